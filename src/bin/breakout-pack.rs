@@ -0,0 +1,138 @@
+//! Packs a directory of assets into a single archive the game can mount, or checks an archive.
+//!
+//! ```text
+//! breakout-pack <assets dir> <archive.pak> [--store]
+//! breakout-pack --check <archive.pak>
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+// Reading archives from the game is only partly needed here
+#[path = "../engine/archive.rs"]
+#[allow(dead_code)]
+mod archive;
+
+use archive::{Archive, ArchiveWriter, Compression};
+
+const USAGE: &str = "usage: breakout-pack <assets dir> <archive.pak> [--store]
+       breakout-pack --check <archive.pak>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args[..] {
+        ["--check", archive] => check(archive),
+        [dir, archive] => pack(dir, archive, Compression::Deflate),
+        [dir, archive, "--store"] => pack(dir, archive, Compression::Stored),
+        _ => Err(String::from(USAGE)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn pack(dir: &str, archive_path: &str, compression: Compression) -> Result<(), String> {
+    let root = Path::new(dir);
+    let mut files = vec![];
+    collect_files(root, &mut files)?;
+    files.sort();
+
+    // An archive written inside the directory it packs would otherwise end up packing itself
+    let archive_path = Path::new(archive_path);
+    let output = archive_path.canonicalize().ok();
+    let mut writer = ArchiveWriter::new();
+    let mut size = 0;
+
+    for file in files {
+        if output.is_some() && file.canonicalize().ok() == output {
+            continue;
+        }
+
+        let name = file
+            .strip_prefix(root)
+            .map_err(|e| e.to_string())?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let contents = fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+
+        size += contents.len();
+        writer
+            .add(&name, &contents, compression)
+            .map_err(|e| e.to_string())?;
+    }
+
+    if writer.is_empty() {
+        return Err(format!("{}: no files to pack", dir));
+    }
+
+    writer.write(archive_path).map_err(|e| e.to_string())?;
+
+    println!(
+        "Packed {} files into {}, {} bytes stored for {} bytes",
+        writer.len(),
+        archive_path.display(),
+        writer.stored_size(),
+        size
+    );
+
+    Ok(())
+}
+
+/// Reads every file back, which verifies all checksums
+fn check(archive_path: &str) -> Result<(), String> {
+    let archive = Archive::open(archive_path).map_err(|e| e.to_string())?;
+    let mut names: Vec<&str> = archive.names().collect();
+    names.sort_unstable();
+
+    let mut corrupted = 0;
+
+    for name in &names {
+        match archive.read(name) {
+            Ok(contents) => println!("{:>10}  {}", contents.len(), name),
+            Err(e) => {
+                eprintln!("{}", e);
+                corrupted += 1;
+            }
+        }
+    }
+
+    match corrupted {
+        0 => {
+            println!("{} files, all intact", names.len());
+            Ok(())
+        }
+        _ => Err(format!(
+            "{} of {} files are corrupted",
+            corrupted,
+            names.len()
+        )),
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use super::{error::Error, resource_manager::atlas::TextureRegion};
+
+/// What happens once the last frame of a clip has been shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Plays backwards to the first frame, then forwards again
+    PingPong,
+    /// Stays on the last frame
+    Once,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub region: TextureRegion,
+    /// Seconds the frame is shown
+    pub duration: f32,
+}
+
+/// A sequence of frames, usually the regions of a sprite sheet. A clip always has a frame.
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    frames: Vec<AnimationFrame>,
+    mode: PlaybackMode,
+}
+
+impl AnimationClip {
+    pub fn new(frames: Vec<AnimationFrame>, mode: PlaybackMode) -> Result<Self, Error> {
+        if frames.is_empty() {
+            return Err(Error::Other(String::from(
+                "An animation clip needs at least one frame",
+            )));
+        }
+
+        Ok(Self { frames, mode })
+    }
+
+    /// Clip where every frame is shown for the same `frame_duration` seconds
+    pub fn uniform(
+        regions: &[TextureRegion],
+        frame_duration: f32,
+        mode: PlaybackMode,
+    ) -> Result<Self, Error> {
+        let frames = regions
+            .iter()
+            .map(|region| AnimationFrame {
+                region: region.clone(),
+                duration: frame_duration,
+            })
+            .collect();
+
+        Self::new(frames, mode)
+    }
+
+    pub fn first_region(&self) -> &TextureRegion {
+        &self.frames[0].region
+    }
+}
+
+/// Plays one of a set of named clips, advanced by `update` at the simulation rate
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    clips: HashMap<String, AnimationClip>,
+    current: Option<String>,
+    frame: usize,
+    /// Seconds the current frame has been shown for
+    elapsed: f32,
+    /// Whether a ping-pong clip is currently playing backwards
+    reversed: bool,
+    finished: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self {
+            clips: HashMap::new(),
+            current: None,
+            frame: 0,
+            elapsed: 0.0,
+            reversed: false,
+            finished: false,
+        }
+    }
+
+    /// Player with a single clip which starts playing right away
+    pub fn with_clip(name: &str, clip: AnimationClip) -> Self {
+        let mut player = Self::new();
+        player.add_clip(name, clip);
+        player.play(name);
+
+        player
+    }
+
+    /// Replacing the clip currently playing starts it over
+    pub fn add_clip(&mut self, name: &str, clip: AnimationClip) {
+        self.clips.insert(name.to_string(), clip);
+
+        if self.current.as_deref() == Some(name) {
+            self.restart();
+        }
+    }
+
+    /// Switches to the clip called `name` from its first frame. Asking for the clip already
+    /// playing keeps it going so this can be called on every update.
+    pub fn play(&mut self, name: &str) {
+        if self.current.as_deref() == Some(name) || !self.clips.contains_key(name) {
+            return;
+        }
+
+        self.current = Some(name.to_string());
+        self.restart();
+    }
+
+    /// Starts the current clip over
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.reversed = false;
+        self.finished = false;
+    }
+
+    /// Whether a clip played once has reached its end, looping clips never finish
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Region of the frame to draw
+    pub fn region(&self) -> Option<&TextureRegion> {
+        self.clip()
+            .and_then(|clip| clip.frames.get(self.frame))
+            .map(|frame| &frame.region)
+    }
+
+    /// Advances the animation by `delta_time` seconds, skipping frames when needed
+    pub fn update(&mut self, delta_time: f32) {
+        if self.finished {
+            return;
+        }
+
+        let clip = match self.current.as_ref().and_then(|name| self.clips.get(name)) {
+            Some(clip) => clip,
+            None => return,
+        };
+
+        // A clip made only of zero length frames would never let the loop below end
+        if clip.frames.iter().all(|frame| frame.duration <= 0.0) {
+            return;
+        }
+
+        self.elapsed += delta_time;
+
+        while self.elapsed >= clip.frames[self.frame].duration {
+            self.elapsed -= clip.frames[self.frame].duration;
+
+            if !advance(
+                clip.mode,
+                clip.frames.len(),
+                &mut self.frame,
+                &mut self.reversed,
+            ) {
+                self.elapsed = 0.0;
+                self.finished = true;
+                break;
+            }
+        }
+    }
+
+    fn clip(&self) -> Option<&AnimationClip> {
+        self.current.as_ref().and_then(|name| self.clips.get(name))
+    }
+}
+
+/// Moves `frame` to the next frame to show, returns false when a clip played once is over
+fn advance(mode: PlaybackMode, frame_count: usize, frame: &mut usize, reversed: &mut bool) -> bool {
+    let last = frame_count - 1;
+
+    match mode {
+        PlaybackMode::Once if *frame == last => return false,
+        PlaybackMode::Once => *frame += 1,
+        PlaybackMode::PingPong if last == 0 => (),
+        PlaybackMode::PingPong => {
+            if *reversed && *frame == 0 || !*reversed && *frame == last {
+                *reversed = !*reversed;
+            }
+
+            if *reversed {
+                *frame -= 1;
+            } else {
+                *frame += 1;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames visited after the first one, `None` once a clip played once is over
+    fn play(mode: PlaybackMode, frame_count: usize, steps: usize) -> Vec<Option<usize>> {
+        let mut frame = 0;
+        let mut reversed = false;
+
+        (0..steps)
+            .map(|_| advance(mode, frame_count, &mut frame, &mut reversed).then_some(frame))
+            .collect()
+    }
+
+    #[test]
+    fn ping_pong_turns_around_at_both_ends() {
+        assert_eq!(
+            play(PlaybackMode::PingPong, 3, 6),
+            [Some(1), Some(2), Some(1), Some(0), Some(1), Some(2)]
+        );
+    }
+
+    #[test]
+    fn ping_pong_with_one_frame_stays() {
+        assert_eq!(play(PlaybackMode::PingPong, 1, 2), [Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        assert_eq!(
+            play(PlaybackMode::Once, 3, 4),
+            [Some(1), Some(2), None, None]
+        );
+    }
+
+    #[test]
+    fn clip_without_frames_is_rejected() {
+        assert!(AnimationClip::new(vec![], PlaybackMode::PingPong).is_err());
+        assert!(AnimationClip::uniform(&[], 0.1, PlaybackMode::Once).is_err());
+    }
+}
@@ -0,0 +1,661 @@
+//! Single file asset archives. An archive starts with a header and an index of its files,
+//! followed by their contents, every number is little endian:
+//!
+//! ```text
+//! magic "BPAK" | version u32 | file count u32 | index size u32 | index CRC-32 u32
+//! index, per file: path size u16 | path | compression u8 | offset u64 | stored size u64
+//!                  | size u64 | CRC-32 u32
+//! contents, offsets are relative to the end of the index
+//! ```
+//!
+//! Checksums are over the index and over the uncompressed contents of every file, so
+//! corruption is found when the archive is mounted or when a file is read respectively.
+//! Kept free of other engine modules as `breakout-pack` builds it on its own.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+
+/// Extension asset archives are recognized by, breakout-pack leaves naming to the user
+pub const EXTENSION: &str = "pak";
+
+const MAGIC: [u8; 4] = *b"BPAK";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Stored = 0,
+    Deflate = 1,
+}
+
+/// Why an archive couldn't be opened or written, or a file in it read
+#[derive(Debug)]
+pub struct ArchiveError {
+    /// The archive, or a file in it as `<name> in <archive>`
+    location: String,
+    kind: ArchiveErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ArchiveErrorKind {
+    Io(io::Error),
+    /// Not an archive, or one of a version this build doesn't read
+    Unsupported(String),
+    /// Truncated, failing a checksum or holding values that can't be right
+    Corrupt(String),
+    /// A file the archive doesn't have
+    NotFound(String),
+    /// A path which can't be stored in an archive
+    InvalidName(String),
+}
+
+impl ArchiveError {
+    fn new(location: impl fmt::Display, kind: ArchiveErrorKind) -> Self {
+        Self {
+            location: location.to_string(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.location)?;
+
+        match &self.kind {
+            ArchiveErrorKind::Io(e) => write!(f, "{}", e),
+            ArchiveErrorKind::NotFound(name) => write!(f, "no file called {}", name),
+            ArchiveErrorKind::Unsupported(message)
+            | ArchiveErrorKind::Corrupt(message)
+            | ArchiveErrorKind::InvalidName(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ArchiveErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveErrorKind {
+    fn from(e: io::Error) -> Self {
+        ArchiveErrorKind::Io(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    compression: Compression,
+    offset: u64,
+    stored_size: u64,
+    size: u64,
+    crc: u32,
+}
+
+/// A mounted archive, its index is kept in memory and files are read from disk when asked for
+pub struct Archive {
+    path: PathBuf,
+    /// Where the contents start
+    data_start: u64,
+    entries: HashMap<String, Entry>,
+}
+
+impl Archive {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ArchiveError> {
+        let path = path.as_ref();
+        let (entries, index_size) = File::open(path)
+            .and_then(|file| Ok((file.metadata()?.len(), file)))
+            .map_err(ArchiveErrorKind::Io)
+            .and_then(|(file_size, file)| read_index(BufReader::new(file), file_size))
+            .map_err(|kind| ArchiveError::new(path.display(), kind))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            data_start: HEADER_SIZE + index_size,
+            entries,
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Paths of all files in the archive, in no particular order, only breakout-pack lists them
+    #[allow(dead_code)]
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Reads and decompresses a file, failing if it doesn't match its checksum
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, ArchiveError> {
+        let entry = self.entries.get(name).ok_or_else(|| {
+            ArchiveError::new(
+                self.path.display(),
+                ArchiveErrorKind::NotFound(name.to_string()),
+            )
+        })?;
+
+        self.read_entry(entry)
+            .map_err(|kind| ArchiveError::new(format!("{} in {}", name, self.path.display()), kind))
+    }
+
+    fn read_entry(&self, entry: &Entry) -> Result<Vec<u8>, ArchiveErrorKind> {
+        let start = self
+            .data_start
+            .checked_add(entry.offset)
+            .ok_or_else(|| corrupt("a file starts past the end of the archive"))?;
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+
+        // Sizes come from the file and aren't trusted, nothing is allocated up front and
+        // reading stops one byte past the expected size so a bad entry can't exhaust memory
+        let stored = file.take(entry.stored_size);
+        let limit = entry.size.saturating_add(1);
+        let mut contents = vec![];
+
+        match entry.compression {
+            Compression::Stored => stored.take(limit).read_to_end(&mut contents),
+            Compression::Deflate => DeflateDecoder::new(stored)
+                .take(limit)
+                .read_to_end(&mut contents),
+        }?;
+
+        if contents.len() as u64 != entry.size || crc32fast::hash(&contents) != entry.crc {
+            return Err(ArchiveErrorKind::Corrupt(String::from(
+                "contents are corrupted",
+            )));
+        }
+
+        Ok(contents)
+    }
+}
+
+/// Only the location and size, the whole index is too much to print
+impl fmt::Debug for Archive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Archive")
+            .field("path", &self.path)
+            .field("files", &self.entries.len())
+            .finish()
+    }
+}
+
+/// Archives with the same location are the same archive
+impl PartialEq for Archive {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for Archive {}
+
+/// Collects files in memory and writes them out as an archive, only breakout-pack uses it
+#[derive(Default)]
+#[allow(dead_code)]
+pub struct ArchiveWriter {
+    entries: Vec<(String, Entry)>,
+    data: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl ArchiveWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file under `name`, a relative path using `/`. Deflate is only used when it
+    /// makes the file smaller, which it often doesn't for already compressed images and sounds.
+    pub fn add(
+        &mut self,
+        name: &str,
+        contents: &[u8],
+        compression: Compression,
+    ) -> Result<(), ArchiveError> {
+        let invalid =
+            |message: &str| ArchiveError::new(name, ArchiveErrorKind::InvalidName(message.into()));
+
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|c| c == "..") {
+            return Err(invalid("paths have to be relative to the archive"));
+        }
+
+        if name.len() > u16::MAX as usize {
+            return Err(invalid("path is too long"));
+        }
+
+        if self.entries.iter().any(|(existing, _)| existing == name) {
+            return Err(invalid("added twice"));
+        }
+
+        let deflated = match compression {
+            Compression::Stored => None,
+            Compression::Deflate => {
+                let mut encoder = DeflateEncoder::new(vec![], flate2::Compression::best());
+                encoder
+                    .write_all(contents)
+                    .and_then(|_| encoder.finish())
+                    .map(Some)
+                    .map_err(|e| ArchiveError::new(name, ArchiveErrorKind::Io(e)))?
+            }
+        };
+
+        let (compression, stored) = match &deflated {
+            Some(deflated) if deflated.len() < contents.len() => {
+                (Compression::Deflate, &deflated[..])
+            }
+            _ => (Compression::Stored, contents),
+        };
+
+        self.entries.push((
+            name.to_string(),
+            Entry {
+                compression,
+                offset: self.data.len() as u64,
+                stored_size: stored.len() as u64,
+                size: contents.len() as u64,
+                crc: crc32fast::hash(contents),
+            },
+        ));
+        self.data.extend_from_slice(stored);
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Size of the contents once written, without the index
+    pub fn stored_size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ArchiveError> {
+        let path = path.as_ref();
+        let mut index = vec![];
+
+        for (name, entry) in &self.entries {
+            index.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            index.extend_from_slice(name.as_bytes());
+            index.push(entry.compression as u8);
+            index.extend_from_slice(&entry.offset.to_le_bytes());
+            index.extend_from_slice(&entry.stored_size.to_le_bytes());
+            index.extend_from_slice(&entry.size.to_le_bytes());
+            index.extend_from_slice(&entry.crc.to_le_bytes());
+        }
+
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(index.len() as u32).to_le_bytes());
+        header.extend_from_slice(&crc32fast::hash(&index).to_le_bytes());
+
+        File::create(path)
+            .and_then(|mut file| {
+                file.write_all(&header)?;
+                file.write_all(&index)?;
+                file.write_all(&self.data)
+            })
+            .map_err(|e| ArchiveError::new(path.display(), ArchiveErrorKind::Io(e)))
+    }
+}
+
+/// Reads the header and the index, returns the files and the size of the index
+fn read_index(
+    mut file: impl Read,
+    file_size: u64,
+) -> Result<(HashMap<String, Entry>, u64), ArchiveErrorKind> {
+    let mut header = [0; HEADER_SIZE as usize];
+    file.read_exact(&mut header).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => corrupt("the header is truncated"),
+        _ => ArchiveErrorKind::Io(e),
+    })?;
+
+    let mut header = Cursor(&header[..]);
+
+    if header.bytes(4)? != MAGIC {
+        return Err(ArchiveErrorKind::Unsupported(String::from(
+            "not an asset archive",
+        )));
+    }
+
+    let version = header.u32()?;
+
+    if version != VERSION {
+        return Err(ArchiveErrorKind::Unsupported(format!(
+            "unsupported archive version {}",
+            version
+        )));
+    }
+
+    let count = header.u32()?;
+    let index_size = header.u32()?;
+    let index_crc = header.u32()?;
+
+    // The header is read before it's checked, so the sizes in it could be anything
+    if HEADER_SIZE + index_size as u64 > file_size {
+        return Err(corrupt("the index is truncated"));
+    }
+
+    let mut index = vec![0; index_size as usize];
+    file.read_exact(&mut index)?;
+
+    if crc32fast::hash(&index) != index_crc {
+        return Err(corrupt("the index is corrupted"));
+    }
+
+    let mut index = Cursor(&index[..]);
+    let mut entries = HashMap::new();
+
+    for _ in 0..count {
+        let name_size = index.u16()? as usize;
+        let name = String::from_utf8(index.bytes(name_size)?.to_vec())
+            .map_err(|_| corrupt("a path isn't UTF-8"))?;
+        let compression = match index.u8()? {
+            0 => Compression::Stored,
+            1 => Compression::Deflate,
+            other => {
+                return Err(ArchiveErrorKind::Unsupported(format!(
+                    "{} uses unknown compression {}",
+                    name, other
+                )))
+            }
+        };
+
+        let entry = Entry {
+            compression,
+            offset: index.u64()?,
+            stored_size: index.u64()?,
+            size: index.u64()?,
+            crc: index.u32()?,
+        };
+
+        // A second entry would silently hide the first one
+        if entries.insert(name, entry).is_some() {
+            return Err(corrupt("a path is in the index twice"));
+        }
+    }
+
+    Ok((entries, index_size as u64))
+}
+
+fn corrupt(message: &str) -> ArchiveErrorKind {
+    ArchiveErrorKind::Corrupt(message.to_string())
+}
+
+/// Reads little endian numbers off the front of a byte slice
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], ArchiveErrorKind> {
+        if self.0.len() < count {
+            return Err(corrupt("the index is truncated"));
+        }
+
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ArchiveErrorKind> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ArchiveErrorKind> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ArchiveErrorKind> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ArchiveErrorKind> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Compresses well, so `Deflate` is actually used for it
+    fn text() -> Vec<u8> {
+        b"all work and no play makes jack a dull boy\n".repeat(50)
+    }
+
+    fn write_archive(dir: &Path, files: &[(&str, &[u8], Compression)]) -> PathBuf {
+        let path = dir.join("test.pak");
+        let mut writer = ArchiveWriter::new();
+
+        for (name, contents, compression) in files {
+            writer.add(name, contents, *compression).unwrap();
+        }
+        writer.write(&path).unwrap();
+
+        path
+    }
+
+    fn flip_byte(path: &Path, position: usize) {
+        let mut bytes = fs::read(path).unwrap();
+        bytes[position] ^= 0xff;
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn round_trips_stored_and_deflated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = text();
+        let path = write_archive(
+            dir.path(),
+            &[
+                ("levels/1.level", &text, Compression::Stored),
+                ("levels/2.level", &text, Compression::Deflate),
+                ("empty", b"", Compression::Deflate),
+            ],
+        );
+
+        let archive = Archive::open(&path).unwrap();
+
+        assert_eq!(
+            archive.entries["levels/1.level"].compression,
+            Compression::Stored
+        );
+        assert_eq!(
+            archive.entries["levels/2.level"].compression,
+            Compression::Deflate
+        );
+        assert_eq!(archive.read("levels/1.level").unwrap(), text);
+        assert_eq!(archive.read("levels/2.level").unwrap(), text);
+        assert_eq!(archive.read("empty").unwrap(), b"");
+        assert!(matches!(
+            archive.read("levels/3.level").unwrap_err().kind,
+            ArchiveErrorKind::NotFound(_)
+        ));
+
+        let mut names: Vec<_> = archive.names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["empty", "levels/1.level", "levels/2.level"]);
+    }
+
+    #[test]
+    fn stores_files_deflate_does_not_shrink() {
+        let mut writer = ArchiveWriter::new();
+        writer
+            .add("noise", &[7, 1, 250], Compression::Deflate)
+            .unwrap();
+
+        assert_eq!(writer.entries[0].1.compression, Compression::Stored);
+        assert_eq!(writer.stored_size(), 3);
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_the_archive() {
+        let mut writer = ArchiveWriter::new();
+
+        assert!(writer.add("", b"", Compression::Stored).is_err());
+        assert!(writer.add("/etc/passwd", b"", Compression::Stored).is_err());
+        assert!(writer.add("a/../../b", b"", Compression::Stored).is_err());
+        assert!(writer.is_empty());
+    }
+
+    #[test]
+    fn detects_a_corrupted_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_archive(dir.path(), &[("a", &text(), Compression::Stored)]);
+        // The first byte of the path, right after its size
+        flip_byte(&path, HEADER_SIZE as usize + 2);
+
+        let error = Archive::open(&path).unwrap_err();
+
+        assert!(matches!(error.kind, ArchiveErrorKind::Corrupt(_)));
+        assert!(
+            error.to_string().ends_with("the index is corrupted"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn detects_corrupted_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = text();
+        let path = write_archive(
+            dir.path(),
+            &[
+                ("stored", &text, Compression::Stored),
+                ("deflated", &text, Compression::Deflate),
+            ],
+        );
+        let archive = Archive::open(&path).unwrap();
+        let stored_start = (archive.data_start + archive.entries["stored"].offset) as usize;
+        let deflated_start = (archive.data_start + archive.entries["deflated"].offset) as usize;
+
+        flip_byte(&path, stored_start + 10);
+        flip_byte(&path, deflated_start + 10);
+
+        let error = archive.read("stored").unwrap_err();
+        assert!(matches!(error.kind, ArchiveErrorKind::Corrupt(_)));
+        assert!(error.to_string().starts_with("stored in "), "{}", error);
+        assert!(
+            error.to_string().ends_with("contents are corrupted"),
+            "{}",
+            error
+        );
+        assert!(archive.read("deflated").is_err());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, b"these are not the assets you're looking for").unwrap();
+
+        let error = Archive::open(&path).unwrap_err();
+
+        assert!(matches!(error.kind, ArchiveErrorKind::Unsupported(_)));
+    }
+
+    #[test]
+    fn reports_a_truncated_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("short.pak");
+        fs::write(&path, b"BPAK\x01\x00").unwrap();
+
+        let error = Archive::open(&path).unwrap_err();
+
+        assert!(matches!(error.kind, ArchiveErrorKind::Corrupt(_)));
+        assert_eq!(
+            error.to_string(),
+            format!("{}: the header is truncated", path.display())
+        );
+    }
+
+    #[test]
+    fn rejects_sizes_larger_than_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("huge.pak");
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        fs::write(&path, header).unwrap();
+
+        let error = Archive::open(&path).unwrap_err();
+
+        assert!(matches!(error.kind, ArchiveErrorKind::Corrupt(_)));
+        assert!(
+            error.to_string().ends_with("the index is truncated"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn does_not_trust_the_size_of_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_archive(dir.path(), &[("a", &text(), Compression::Deflate)]);
+        let mut archive = Archive::open(&path).unwrap();
+        archive.entries.get_mut("a").unwrap().size = u64::MAX;
+
+        let error = archive.read("a").unwrap_err();
+
+        assert!(matches!(error.kind, ArchiveErrorKind::Corrupt(_)));
+    }
+    #[test]
+    fn rejects_offsets_past_the_end_of_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_archive(dir.path(), &[("a", &text(), Compression::Stored)]);
+        let mut archive = Archive::open(&path).unwrap();
+        archive.entries.get_mut("a").unwrap().offset = u64::MAX;
+
+        let error = archive.read("a").unwrap_err();
+
+        assert!(matches!(error.kind, ArchiveErrorKind::Corrupt(_)));
+    }
+
+    #[test]
+    fn rejects_a_path_listed_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_archive(
+            dir.path(),
+            &[
+                ("a", b"first", Compression::Stored),
+                ("b", b"second", Compression::Stored),
+            ],
+        );
+
+        // Renames "b" to "a", entries take 32 bytes with a one byte path, and fixes the
+        // index checksum so only the duplicate is wrong
+        let mut bytes = fs::read(&path).unwrap();
+        let index_end = HEADER_SIZE as usize + 64;
+        bytes[HEADER_SIZE as usize + 34] = b'a';
+        let crc = crc32fast::hash(&bytes[HEADER_SIZE as usize..index_end]);
+        bytes[16..20].copy_from_slice(&crc.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+
+        let error = Archive::open(&path).unwrap_err();
+
+        assert!(matches!(error.kind, ArchiveErrorKind::Corrupt(_)));
+        assert!(error.to_string().ends_with("twice"), "{}", error);
+    }
+}
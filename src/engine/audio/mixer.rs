@@ -0,0 +1,398 @@
+use std::sync::Arc;
+
+use sdl2::audio::AudioCallback;
+
+use super::music_stream::MusicFeed;
+
+/// Effects beyond this many playing at once replace the oldest one
+const MAX_VOICES: usize = 32;
+
+/// A sound effect being played
+struct Voice {
+    samples: Arc<[f32]>,
+    /// Position in stereo frames, fractional as the sound is resampled while playing
+    position: f64,
+    /// Source frames advanced per output frame
+    step: f64,
+    volume: f32,
+}
+
+impl Voice {
+    /// Adds the voice to `out`, returns false once the sound has ended
+    fn mix(&mut self, out: &mut [f32]) -> bool {
+        let frames = self.samples.len() / 2;
+
+        for frame in out.chunks_exact_mut(2) {
+            let index = self.position as usize;
+
+            if index + 1 >= frames {
+                return false;
+            }
+
+            let [left, right] = interpolate(&self.samples, index, self.position.fract() as f32);
+            frame[0] += left * self.volume;
+            frame[1] += right * self.volume;
+
+            self.position += self.step;
+        }
+
+        true
+    }
+}
+
+/// Moves a gain towards a target over time
+#[derive(Clone, Copy)]
+struct Fade {
+    from: f32,
+    to: f32,
+    /// In output frames
+    duration: u32,
+    elapsed: u32,
+}
+
+impl Fade {
+    fn gain(&self) -> f32 {
+        if self.elapsed >= self.duration {
+            self.to
+        } else {
+            self.from + (self.to - self.from) * self.elapsed as f32 / self.duration as f32
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Music being played, decoded ahead on a thread of its own
+struct MusicTrack {
+    feed: MusicFeed,
+    /// The two decoded frames the position lies between
+    frames: [[f32; 2]; 2],
+    /// From 0.0 at the first of `frames` towards 1.0 at the second
+    position: f64,
+    step: f64,
+    fade: Fade,
+}
+
+impl MusicTrack {
+    /// Adds the track to `out`, returns false once it's silent for good
+    fn mix(&mut self, out: &mut [f32]) -> bool {
+        for frame in out.chunks_exact_mut(2) {
+            let Some([left, right]) = self.next_frame() else {
+                return false;
+            };
+
+            let gain = self.fade.gain();
+            frame[0] += left * gain;
+            frame[1] += right * gain;
+
+            self.fade.elapsed = self.fade.elapsed.saturating_add(1);
+        }
+
+        !(self.fade.is_done() && self.fade.to == 0.0)
+    }
+
+    fn next_frame(&mut self) -> Option<[f32; 2]> {
+        while self.position >= 1.0 {
+            match self.feed.next_frame() {
+                Some(frame) => {
+                    self.frames = [self.frames[1], frame];
+                    self.position -= 1.0;
+                }
+                None if self.feed.is_finished() => return None,
+                // The decoder fell behind, a moment of silence beats waiting for it here
+                None => return Some([0.0, 0.0]),
+            }
+        }
+
+        let t = self.position as f32;
+        let [current, next] = self.frames;
+        self.position += self.step;
+
+        Some([
+            current[0] + (next[0] - current[0]) * t,
+            current[1] + (next[1] - current[1]) * t,
+        ])
+    }
+}
+
+/// Mixes every playing sound effect and music track into the output of the audio device,
+/// runs on the audio thread and is only touched from elsewhere through the device lock.
+/// Mixing never reads files or decodes, music arrives already decoded through `MusicFeed`.
+pub struct Mixer {
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    music: Vec<MusicTrack>,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            voices: vec![],
+            music: vec![],
+        }
+    }
+
+    /// `pitch` speeds the sound up or down, changing its length along with it
+    pub fn play(&mut self, samples: Arc<[f32]>, sample_rate: u32, volume: f32, pitch: f32) {
+        if self.voices.len() >= MAX_VOICES {
+            self.voices.remove(0);
+        }
+
+        self.voices.push(Voice {
+            samples,
+            position: 0.0,
+            step: sample_rate as f64 / self.sample_rate as f64 * pitch.max(0.01) as f64,
+            volume,
+        });
+    }
+
+    /// Starts looping `feed`, whatever was playing before fades out while it fades in
+    pub fn play_music(&mut self, feed: MusicFeed, crossfade: f32) {
+        self.fade_out_music(crossfade);
+
+        let duration = self.seconds_to_frames(crossfade);
+
+        self.music.push(MusicTrack {
+            step: feed.sample_rate() as f64 / self.sample_rate as f64,
+            feed,
+            frames: [[0.0; 2]; 2],
+            // Both frames are taken from the feed before the first one is played
+            position: 2.0,
+            fade: Fade {
+                from: 0.0,
+                to: 1.0,
+                duration,
+                elapsed: 0,
+            },
+        });
+    }
+
+    pub fn fade_out_music(&mut self, duration: f32) {
+        let duration = self.seconds_to_frames(duration);
+
+        for track in self.music.iter_mut() {
+            track.fade = Fade {
+                from: track.fade.gain(),
+                to: 0.0,
+                duration,
+                elapsed: 0,
+            };
+        }
+
+        self.music.retain(|track| !track.fade.is_done());
+    }
+
+    fn seconds_to_frames(&self, seconds: f32) -> u32 {
+        (seconds.max(0.0) * self.sample_rate as f32) as u32
+    }
+}
+
+impl AudioCallback for Mixer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+
+        self.voices.retain_mut(|voice| voice.mix(out));
+        self.music.retain_mut(|track| track.mix(out));
+
+        for sample in out.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// Linear interpolation between stereo frame `index` and the one after it
+fn interpolate(samples: &[f32], index: usize, t: f32) -> [f32; 2] {
+    let current = &samples[index * 2..index * 2 + 2];
+    let next = &samples[index * 2 + 2..index * 2 + 4];
+
+    [
+        current[0] + (next[0] - current[0]) * t,
+        current[1] + (next[1] - current[1]) * t,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        audio::music_stream::tests::wait_for_frames, error::Error,
+        resource_manager::sound::SampleStream,
+    };
+
+    const RATE: u32 = 1000;
+
+    /// The same frame over and over
+    struct ConstantStream(f32);
+
+    impl SampleStream for ConstantStream {
+        fn sample_rate(&self) -> u32 {
+            RATE
+        }
+
+        fn read(&mut self, out: &mut Vec<f32>) -> Result<usize, Error> {
+            out.extend([self.0; 200]);
+            Ok(100)
+        }
+
+        fn rewind(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn play_constant_music(mixer: &mut Mixer, value: f32, crossfade: f32) {
+        mixer.play_music(MusicFeed::spawn(Box::new(ConstantStream(value))), crossfade);
+        wait_for_frames(&mixer.music.last().unwrap().feed, 1000);
+    }
+
+    fn mix(mixer: &mut Mixer, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.0; frames * 2];
+        mixer.callback(&mut out);
+        out
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn mixes_voices_with_their_volume() {
+        let mut mixer = Mixer::new(RATE);
+        mixer.play(Arc::from([0.5; 20]), RATE, 0.5, 1.0);
+        mixer.play(Arc::from([0.25; 20]), RATE, 1.0, 1.0);
+
+        let out = mix(&mut mixer, 4);
+
+        for sample in out {
+            assert_close(sample, 0.5);
+        }
+    }
+
+    #[test]
+    fn clamps_the_mix() {
+        let mut mixer = Mixer::new(RATE);
+        mixer.play(Arc::from([0.8; 20]), RATE, 1.0, 1.0);
+        mixer.play(Arc::from([-0.8, 0.8].repeat(10)), RATE, 1.0, 1.0);
+        mixer.play(Arc::from([0.8; 20]), RATE, 1.0, 1.0);
+
+        let out = mix(&mut mixer, 2);
+
+        assert_eq!(out, [0.8, 1.0, 0.8, 1.0]);
+    }
+
+    #[test]
+    fn removes_finished_voices() {
+        let mut mixer = Mixer::new(RATE);
+        mixer.play(Arc::from([1.0; 10]), RATE, 1.0, 1.0);
+
+        let out = mix(&mut mixer, 8);
+
+        assert!(mixer.voices.is_empty());
+        // The last frame has nothing to interpolate towards and isn't played
+        assert_eq!(out[..8], [1.0; 8]);
+        assert_eq!(out[8..], [0.0; 8]);
+    }
+
+    #[test]
+    fn resamples_and_pitches_voices() {
+        let mut mixer = Mixer::new(RATE);
+        mixer.play(Arc::from([0.0; 100]), RATE * 2, 1.0, 1.0);
+        mixer.play(Arc::from([0.0; 100]), RATE, 1.0, 0.5);
+
+        assert_close(mixer.voices[0].step as f32, 2.0);
+        assert_close(mixer.voices[1].step as f32, 0.5);
+    }
+
+    #[test]
+    fn replaces_the_oldest_voice() {
+        let mut mixer = Mixer::new(RATE);
+
+        for volume in 0..MAX_VOICES + 1 {
+            mixer.play(Arc::from([0.0; 4]), RATE, volume as f32, 1.0);
+        }
+
+        assert_eq!(mixer.voices.len(), MAX_VOICES);
+        assert_eq!(mixer.voices[0].volume, 1.0);
+    }
+
+    #[test]
+    fn fade_moves_towards_its_target() {
+        let mut fade = Fade {
+            from: 1.0,
+            to: 0.0,
+            duration: 4,
+            elapsed: 0,
+        };
+
+        assert_close(fade.gain(), 1.0);
+        fade.elapsed = 1;
+        assert_close(fade.gain(), 0.75);
+        fade.elapsed = 4;
+        assert_close(fade.gain(), 0.0);
+        assert!(fade.is_done());
+        fade.elapsed = 10;
+        assert_close(fade.gain(), 0.0);
+    }
+
+    #[test]
+    fn music_fades_in() {
+        let mut mixer = Mixer::new(RATE);
+        play_constant_music(&mut mixer, 0.5, 0.1);
+
+        let out = mix(&mut mixer, 200);
+
+        assert_close(out[0], 0.0);
+        assert_close(out[50 * 2], 0.25);
+        assert_close(out[100 * 2], 0.5);
+        assert_close(out[199 * 2 + 1], 0.5);
+    }
+
+    #[test]
+    fn music_fades_out_and_stops() {
+        let mut mixer = Mixer::new(RATE);
+        play_constant_music(&mut mixer, 0.5, 0.0);
+        mixer.fade_out_music(0.1);
+
+        let out = mix(&mut mixer, 50);
+        assert_close(out[0], 0.5);
+        assert_close(out[25 * 2], 0.375);
+        assert_eq!(mixer.music.len(), 1);
+
+        mix(&mut mixer, 50);
+        assert!(mixer.music.is_empty());
+    }
+
+    #[test]
+    fn music_crossfades() {
+        let mut mixer = Mixer::new(RATE);
+        play_constant_music(&mut mixer, 0.5, 0.0);
+        play_constant_music(&mut mixer, -0.5, 0.1);
+
+        let out = mix(&mut mixer, 100);
+        assert_close(out[0], 0.5);
+        assert_close(out[50 * 2], 0.0);
+        assert_eq!(mixer.music.len(), 1);
+
+        let out = mix(&mut mixer, 1);
+        assert_close(out[0], -0.5);
+    }
+
+    #[test]
+    fn stopping_immediately_drops_the_music() {
+        let mut mixer = Mixer::new(RATE);
+        play_constant_music(&mut mixer, 0.5, 0.0);
+        mixer.fade_out_music(0.0);
+
+        assert!(mixer.music.is_empty());
+        assert_eq!(mix(&mut mixer, 4), [0.0; 8]);
+    }
+}
@@ -0,0 +1,199 @@
+use rand::Rng;
+use sdl2::audio::{AudioDevice, AudioSpecDesired};
+
+use super::{
+    error::Error,
+    resource_manager::sound::{Music, Sound},
+};
+
+use self::{mixer::Mixer, music_stream::MusicFeed};
+
+mod mixer;
+mod music_stream;
+
+const SAMPLE_RATE: i32 = 44100;
+/// Frames per callback, small enough to keep effects in sync with the game
+const BUFFER_FRAMES: u16 = 1024;
+
+/// How a sound effect is played. Variations pick a random offset in `-variation..=variation`
+/// every time the sound plays so repeated effects don't sound mechanical.
+#[derive(Debug, Clone, Copy)]
+pub struct SoundParams {
+    pub volume: f32,
+    pub volume_variation: f32,
+    /// 1.0 is the original pitch, 2.0 an octave up
+    pub pitch: f32,
+    pub pitch_variation: f32,
+}
+
+impl Default for SoundParams {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            volume_variation: 0.0,
+            pitch: 1.0,
+            pitch_variation: 0.0,
+        }
+    }
+}
+
+enum Output {
+    Device(AudioDevice<Mixer>),
+    /// No audio hardware, everything is accepted and nothing is heard
+    Null,
+}
+
+/// Plays sound effects and streams music through the default audio device
+pub struct Audio {
+    output: Output,
+    music: Option<Music>,
+}
+
+impl Audio {
+    /// Opens the default audio device, falls back to a silent null device when there's no
+    /// audio hardware so the game keeps running
+    pub fn build(sdl_context: &sdl2::Sdl) -> Self {
+        match Self::open_device(sdl_context) {
+            Ok(device) => {
+                device.resume();
+
+                Self {
+                    output: Output::Device(device),
+                    music: None,
+                }
+            }
+            Err(e) => {
+                eprintln!("Couldn't open an audio device, audio is disabled: {}", e);
+                Self::null()
+            }
+        }
+    }
+
+    /// Audio without an output device
+    pub fn null() -> Self {
+        Self {
+            output: Output::Null,
+            music: None,
+        }
+    }
+
+    /// Whether sound is actually played
+    pub fn is_null(&self) -> bool {
+        matches!(self.output, Output::Null)
+    }
+
+    pub fn play(&mut self, sound: &Sound, params: &SoundParams) {
+        let mut rng = rand::thread_rng();
+        let volume = params.volume + vary(&mut rng, params.volume_variation);
+        let pitch = params.pitch + vary(&mut rng, params.pitch_variation);
+
+        self.with_mixer(|mixer| {
+            mixer.play(
+                sound.samples().clone(),
+                sound.sample_rate(),
+                volume.max(0.0),
+                pitch,
+            )
+        });
+    }
+
+    /// Loops `music`, crossfading from whatever was playing over `crossfade` seconds.
+    /// Asking for the music already playing does nothing.
+    pub fn play_music(&mut self, music: &Music, crossfade: f32) -> Result<(), Error> {
+        if self.music.as_ref() == Some(music) {
+            return Ok(());
+        }
+
+        if !self.is_null() {
+            // Decoding starts before the lock is taken, the feed is only handed over
+            let feed = MusicFeed::spawn(music.open()?);
+            self.with_mixer(|mixer| mixer.play_music(feed, crossfade));
+        }
+
+        self.music = Some(music.clone());
+
+        Ok(())
+    }
+
+    fn open_device(sdl_context: &sdl2::Sdl) -> Result<AudioDevice<Mixer>, Error> {
+        let audio_subsystem = sdl_context.audio().map_err(Error::Sdl)?;
+
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(2),
+            samples: Some(BUFFER_FRAMES),
+        };
+
+        audio_subsystem
+            .open_playback(None, &desired, |spec| Mixer::new(spec.freq as u32))
+            .map_err(Error::Sdl)
+    }
+
+    /// Runs `f` while holding the lock of the audio thread, does nothing on the null device
+    fn with_mixer(&mut self, f: impl FnOnce(&mut Mixer)) {
+        if let Output::Device(device) = &mut self.output {
+            f(&mut device.lock());
+        }
+    }
+}
+
+fn vary(rng: &mut impl Rng, variation: f32) -> f32 {
+    if variation > 0.0 {
+        rng.gen_range(-variation..=variation)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::vfs::Vfs;
+
+    fn write_wav(path: &std::path::Path) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+
+        for i in 0..2205 {
+            writer.write_sample((i % 100) as i16 * 100).unwrap();
+        }
+
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn null_device_accepts_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("beep.wav"));
+
+        let mut vfs = Vfs::new();
+        vfs.mount_dir("", dir.path());
+        let sound = Sound::from_file(&vfs, "beep.wav").unwrap();
+        let music = Music::from_file(&vfs, "beep.wav").unwrap();
+
+        let mut audio = Audio::null();
+        assert!(audio.is_null());
+
+        audio.play(&sound, &SoundParams::default());
+        audio.play_music(&music, 1.0).unwrap();
+        assert_eq!(audio.music.as_ref(), Some(&music));
+        // The same music again is a no-op, also without a device
+        audio.play_music(&music, 1.0).unwrap();
+    }
+
+    #[test]
+    fn variation_stays_in_range() {
+        let mut rng = rand::thread_rng();
+
+        assert_eq!(vary(&mut rng, 0.0), 0.0);
+
+        for _ in 0..100 {
+            assert!(vary(&mut rng, 0.25).abs() <= 0.25);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::engine::{error::Error, resource_manager::sound::SampleStream};
+
+/// Stereo frames decoded ahead of what's playing, about 0.4s at 44.1kHz
+const BUFFER_FRAMES: usize = 16384;
+/// How long the decoder sleeps when the buffer is full
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Queue of stereo frames with one thread writing and another reading, neither side
+/// ever blocks or allocates
+struct RingBuffer {
+    /// Both samples of a frame packed into one value so a frame is never read half written
+    frames: Box<[AtomicU64]>,
+    /// Frames read and written so far, only the reader moves `read` and only the writer `write`
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+impl RingBuffer {
+    /// `capacity` has to be a power of two so the counters can wrap around
+    fn new(capacity: usize) -> Self {
+        assert!(capacity.is_power_of_two());
+
+        Self {
+            frames: (0..capacity).map(|_| AtomicU64::new(0)).collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        write.wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends as many frames of interleaved stereo `samples` as fit, returns how many that was
+    fn push(&self, samples: &[f32]) -> usize {
+        let write = self.write.load(Ordering::Relaxed);
+        let free = self.frames.len() - write.wrapping_sub(self.read.load(Ordering::Acquire));
+        let mask = self.frames.len() - 1;
+        let mut count = 0;
+
+        for frame in samples.chunks_exact(2).take(free) {
+            let packed = (frame[0].to_bits() as u64) << 32 | frame[1].to_bits() as u64;
+            self.frames[write.wrapping_add(count) & mask].store(packed, Ordering::Relaxed);
+            count += 1;
+        }
+
+        self.write
+            .store(write.wrapping_add(count), Ordering::Release);
+
+        count
+    }
+
+    fn pop(&self) -> Option<[f32; 2]> {
+        let read = self.read.load(Ordering::Relaxed);
+
+        if read == self.write.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let packed = self.frames[read & (self.frames.len() - 1)].load(Ordering::Relaxed);
+        self.read.store(read.wrapping_add(1), Ordering::Release);
+
+        Some([
+            f32::from_bits((packed >> 32) as u32),
+            f32::from_bits(packed as u32),
+        ])
+    }
+}
+
+struct Shared {
+    frames: RingBuffer,
+    /// Set by the decoder once nothing more is coming
+    finished: AtomicBool,
+    /// Set when the feed is dropped, the decoder exits after seeing it
+    stopped: AtomicBool,
+}
+
+/// Music decoded on a thread of its own, looping forever. The audio thread only takes
+/// frames out of it, so a slow disk or decoder can't hold up the audio callback.
+pub struct MusicFeed {
+    shared: Arc<Shared>,
+    sample_rate: u32,
+}
+
+impl MusicFeed {
+    pub fn spawn(stream: Box<dyn SampleStream>) -> Self {
+        let shared = Arc::new(Shared {
+            frames: RingBuffer::new(BUFFER_FRAMES),
+            finished: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        });
+        let sample_rate = stream.sample_rate();
+
+        let decoder_shared = shared.clone();
+        thread::Builder::new()
+            .name(String::from("music decoder"))
+            .spawn(move || decode(stream, &decoder_shared))
+            .expect("Couldn't start a music decoder thread");
+
+        Self {
+            shared,
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The next decoded frame, `None` when the decoder hasn't caught up or has finished
+    pub fn next_frame(&self) -> Option<[f32; 2]> {
+        self.shared.frames.pop()
+    }
+
+    /// Whether every frame the decoder will ever produce was taken, which only happens when
+    /// decoding failed as the music loops otherwise
+    pub fn is_finished(&self) -> bool {
+        // Frames are pushed before `finished` is set, so seeing it means the buffer is final
+        self.shared.finished.load(Ordering::Acquire) && self.shared.frames.is_empty()
+    }
+}
+
+impl Drop for MusicFeed {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Release);
+    }
+}
+
+/// Runs on the decoder thread until the feed is dropped, which keeps the last reference to
+/// the buffer here instead of freeing it on the audio thread
+fn decode(mut stream: Box<dyn SampleStream>, shared: &Shared) {
+    let mut pending = vec![];
+    let mut offset = 0;
+
+    while !shared.stopped.load(Ordering::Acquire) {
+        if shared.finished.load(Ordering::Relaxed) {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        if offset == pending.len() {
+            pending.clear();
+            offset = 0;
+
+            if let Err(e) = read_looping(stream.as_mut(), &mut pending) {
+                eprintln!("Music stopped: {}", e);
+                shared.finished.store(true, Ordering::Release);
+                continue;
+            }
+        }
+
+        offset += shared.frames.push(&pending[offset..]) * 2;
+
+        if offset < pending.len() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Reads the next samples, going back to the start at the end of the stream
+fn read_looping(stream: &mut dyn SampleStream, out: &mut Vec<f32>) -> Result<(), Error> {
+    if stream.read(out)? > 0 {
+        return Ok(());
+    }
+
+    stream.rewind()?;
+
+    // An empty stream would otherwise be rewound forever
+    if stream.read(out)? == 0 {
+        return Err(Error::Other(String::from("Music stream is empty")));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Counts up from 0, one frame per value, `len` frames before looping
+    pub struct CountingStream {
+        pub len: usize,
+        pub position: usize,
+    }
+
+    impl SampleStream for CountingStream {
+        fn sample_rate(&self) -> u32 {
+            44100
+        }
+
+        fn read(&mut self, out: &mut Vec<f32>) -> Result<usize, Error> {
+            let end = self.len.min(self.position + 100);
+            let frames = end - self.position;
+
+            for value in self.position..end {
+                out.extend([value as f32, -(value as f32)]);
+            }
+            self.position = end;
+
+            Ok(frames)
+        }
+
+        fn rewind(&mut self) -> Result<(), Error> {
+            self.position = 0;
+            Ok(())
+        }
+    }
+
+    /// Waits for the decoder to have decoded at least `frames`
+    pub fn wait_for_frames(feed: &MusicFeed, frames: usize) {
+        let start = Instant::now();
+
+        while feed.shared.frames.len() < frames && !feed.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(5), "decoder is stuck");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn ring_buffer_keeps_order_across_the_wrap() {
+        let ring = RingBuffer::new(4);
+
+        assert_eq!(ring.push(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]), 3);
+        assert_eq!(ring.pop(), Some([1.0, 2.0]));
+        assert_eq!(ring.pop(), Some([3.0, 4.0]));
+
+        assert_eq!(ring.push(&[7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0]), 3);
+        assert_eq!(ring.len(), 4);
+        assert_eq!(ring.push(&[15.0, 16.0]), 0);
+
+        let frames: Vec<_> = std::iter::from_fn(|| ring.pop()).collect();
+        assert_eq!(frames, [[5.0, 6.0], [7.0, 8.0], [9.0, 10.0], [11.0, 12.0]]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn ring_buffer_ignores_a_trailing_half_frame() {
+        let ring = RingBuffer::new(4);
+
+        assert_eq!(ring.push(&[1.0, 2.0, 3.0]), 1);
+        assert_eq!(ring.len(), 1);
+    }
+
+    #[test]
+    fn feed_loops_the_stream() {
+        let feed = MusicFeed::spawn(Box::new(CountingStream {
+            len: 150,
+            position: 0,
+        }));
+
+        let mut values = vec![];
+        let start = Instant::now();
+
+        while values.len() < 400 {
+            assert!(start.elapsed() < Duration::from_secs(5), "decoder is stuck");
+
+            match feed.next_frame() {
+                Some([left, right]) => {
+                    assert_eq!(left, -right);
+                    values.push(left as usize);
+                }
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+
+        let expected: Vec<_> = (0..150).cycle().take(400).collect();
+        assert_eq!(values, expected);
+        assert!(!feed.is_finished());
+    }
+
+    #[test]
+    fn empty_stream_finishes() {
+        let feed = MusicFeed::spawn(Box::new(CountingStream {
+            len: 0,
+            position: 0,
+        }));
+        wait_for_frames(&feed, 1);
+
+        assert!(feed.is_finished());
+        assert_eq!(feed.next_frame(), None);
+    }
+}
@@ -0,0 +1,170 @@
+/// Describes how trauma turns into shaking, the shake grows with the square of the trauma
+/// so small hits barely move the camera while big ones throw it around
+#[derive(Debug, Clone, Copy)]
+pub struct ShakeConfig {
+    /// Largest offset in world units at full trauma
+    pub max_offset: glam::Vec2,
+    /// Largest rotation in degrees at full trauma
+    pub max_angle: f32,
+    /// How fast the camera wobbles, roughly in shakes per second
+    pub frequency: f32,
+    /// Trauma removed per second
+    pub decay: f32,
+}
+
+impl Default for ShakeConfig {
+    fn default() -> Self {
+        Self {
+            max_offset: glam::vec2(12.0, 12.0),
+            max_angle: 2.0,
+            frequency: 25.0,
+            decay: 1.5,
+        }
+    }
+}
+
+/// A view onto the world which shakes with trauma. A camera at rest shows the world exactly
+/// as it would be without a camera, shaking moves and turns it around the center of the screen.
+#[derive(Debug, Clone)]
+pub struct Camera2D {
+    /// Logical resolution the camera renders to
+    screen_size: glam::Vec2,
+    trauma: f32,
+    shake: ShakeConfig,
+    /// Seconds of simulated time, drives the shake noise
+    time: f32,
+    shake_offset: glam::Vec2,
+    /// In degrees, like sprite rotations
+    shake_angle: f32,
+}
+
+impl Camera2D {
+    /// `width` and `height` are the logical resolution
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            screen_size: glam::vec2(width as f32, height as f32),
+            trauma: 0.0,
+            shake: ShakeConfig::default(),
+            time: 0.0,
+            shake_offset: glam::Vec2::ZERO,
+            shake_angle: 0.0,
+        }
+    }
+
+    /// Adds trauma between 0 and 1, it wears off over time according to the shake config
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    /// Advances shaking by `delta_time` seconds
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        self.trauma = (self.trauma - self.shake.decay * delta_time).max(0.0);
+
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.shake.frequency;
+
+        self.shake_offset =
+            glam::vec2(noise(t, 0.0), noise(t, 1.0)) * self.shake.max_offset * shake;
+        self.shake_angle = noise(t, 2.0) * self.shake.max_angle * shake;
+    }
+
+    /// Transforms world coordinates into the logical screen coordinates the projection expects
+    pub fn view_matrix(&self) -> glam::Mat4 {
+        let center = (self.screen_size / 2.0).extend(0.0);
+
+        glam::Mat4::from_translation(center)
+            * glam::Mat4::from_rotation_z(-self.shake_angle.to_radians())
+            * glam::Mat4::from_translation(-center - self.shake_offset.extend(0.0))
+    }
+}
+
+/// Smooth pseudo random value in -1.0..1.0, `seed` gives every axis its own motion
+fn noise(t: f32, seed: f32) -> f32 {
+    let phase = seed * 17.13;
+
+    ((t + phase).sin() * 0.5
+        + (t * 2.31 + phase * 1.7).sin() * 0.3
+        + (t * 4.67 + phase * 2.9).sin() * 0.2)
+        .clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_screen(camera: &Camera2D, world: glam::Vec2) -> glam::Vec2 {
+        camera
+            .view_matrix()
+            .transform_point3(world.extend(0.0))
+            .truncate()
+    }
+
+    fn assert_close(actual: glam::Vec2, expected: glam::Vec2) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-3),
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn camera_at_rest_leaves_coordinates_alone() {
+        let camera = Camera2D::new(800, 600);
+
+        assert_close(
+            to_screen(&camera, glam::vec2(0.0, 0.0)),
+            glam::vec2(0.0, 0.0),
+        );
+        assert_close(
+            to_screen(&camera, glam::vec2(123.0, 456.0)),
+            glam::vec2(123.0, 456.0),
+        );
+    }
+
+    #[test]
+    fn shake_offset_moves_the_world_the_other_way() {
+        let mut camera = Camera2D::new(800, 600);
+        camera.shake_offset = glam::vec2(5.0, -3.0);
+
+        assert_close(
+            to_screen(&camera, glam::vec2(100.0, 100.0)),
+            glam::vec2(95.0, 103.0),
+        );
+    }
+
+    #[test]
+    fn shake_angle_turns_around_the_screen_center() {
+        let mut camera = Camera2D::new(800, 600);
+        camera.shake_angle = 90.0;
+
+        assert_close(
+            to_screen(&camera, glam::vec2(400.0, 300.0)),
+            glam::vec2(400.0, 300.0),
+        );
+        assert_close(
+            to_screen(&camera, glam::vec2(500.0, 300.0)),
+            glam::vec2(400.0, 200.0),
+        );
+    }
+
+    #[test]
+    fn trauma_wears_off() {
+        let mut camera = Camera2D::new(800, 600);
+        camera.add_trauma(2.0);
+        assert_eq!(camera.trauma, 1.0);
+
+        camera.update(0.1);
+        assert!(camera.trauma < 1.0);
+        assert!(camera
+            .shake_offset
+            .abs()
+            .cmple(ShakeConfig::default().max_offset)
+            .all());
+
+        camera.update(10.0);
+        assert_eq!(camera.trauma, 0.0);
+        assert_eq!(camera.shake_offset, glam::Vec2::ZERO);
+    }
+}
@@ -0,0 +1,110 @@
+use sdl2::{video::FullscreenType, VideoSubsystem};
+
+use super::error::Error;
+
+/// How the window occupies the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    /// Exclusive fullscreen, switches the monitor to the selected display mode
+    Fullscreen,
+    /// Borderless window covering the whole desktop, keeps the desktop resolution
+    Borderless,
+}
+
+impl WindowMode {
+    pub(super) fn to_sdl(self) -> FullscreenType {
+        match self {
+            WindowMode::Windowed => FullscreenType::Off,
+            WindowMode::Fullscreen => FullscreenType::True,
+            WindowMode::Borderless => FullscreenType::Desktop,
+        }
+    }
+}
+
+/// Resolution and refresh rate a monitor supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: u32,
+}
+
+impl DisplayMode {
+    pub(super) fn from_sdl(mode: sdl2::video::DisplayMode) -> Self {
+        Self {
+            width: mode.w.max(0) as u32,
+            height: mode.h.max(0) as u32,
+            refresh_rate: mode.refresh_rate.max(0) as u32,
+        }
+    }
+
+    pub(super) fn to_sdl(self) -> sdl2::video::DisplayMode {
+        // Unknown pixel format lets SDL pick the closest format the monitor supports
+        sdl2::video::DisplayMode::new(
+            sdl2::pixels::PixelFormatEnum::Unknown,
+            self.width as i32,
+            self.height as i32,
+            self.refresh_rate as i32,
+        )
+    }
+}
+
+/// A connected monitor
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayInfo {
+    pub index: i32,
+    pub name: String,
+    /// Position and size of the monitor on the virtual desktop
+    pub bounds: (i32, i32, u32, u32),
+    /// Diagonal DPI, not every platform is able to report it
+    pub dpi: Option<f32>,
+    pub current_mode: DisplayMode,
+}
+
+pub fn displays(video_subsystem: &VideoSubsystem) -> Result<Vec<DisplayInfo>, Error> {
+    let count = video_subsystem.num_video_displays().map_err(Error::Sdl)?;
+    let mut displays = Vec::with_capacity(count.max(0) as usize);
+
+    for index in 0..count {
+        let bounds = video_subsystem.display_bounds(index).map_err(Error::Sdl)?;
+
+        displays.push(DisplayInfo {
+            index,
+            name: video_subsystem.display_name(index).map_err(Error::Sdl)?,
+            bounds: (bounds.x(), bounds.y(), bounds.width(), bounds.height()),
+            dpi: video_subsystem
+                .display_dpi(index)
+                .ok()
+                .map(|(ddpi, _, _)| ddpi),
+            current_mode: DisplayMode::from_sdl(
+                video_subsystem
+                    .current_display_mode(index)
+                    .map_err(Error::Sdl)?,
+            ),
+        });
+    }
+
+    Ok(displays)
+}
+
+/// Every mode the display supports, SDL sorts them from the largest to the smallest
+pub fn display_modes(
+    video_subsystem: &VideoSubsystem,
+    display_index: i32,
+) -> Result<Vec<DisplayMode>, Error> {
+    let count = video_subsystem
+        .num_display_modes(display_index)
+        .map_err(Error::Sdl)?;
+    let mut modes = Vec::with_capacity(count.max(0) as usize);
+
+    for mode_index in 0..count {
+        modes.push(DisplayMode::from_sdl(
+            video_subsystem
+                .display_mode(display_index, mode_index)
+                .map_err(Error::Sdl)?,
+        ));
+    }
+
+    Ok(modes)
+}
@@ -0,0 +1,152 @@
+use std::{fmt, io};
+
+use super::archive::ArchiveError;
+
+/// Cause of a failure somewhere in the engine. Messages are complete on their own, the
+/// underlying error is also available through `source` for code that needs to look at it.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading a file failed, including files none of the mounts have
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    /// An image couldn't be decoded, `path` is `None` for images decoded from memory
+    Image {
+        path: Option<String>,
+        source: image::ImageError,
+    },
+    ShaderCompile {
+        stage: ShaderStage,
+        path: String,
+        /// What the GLSL compiler reported
+        log: String,
+    },
+    ShaderLink {
+        log: String,
+    },
+    /// A level file with something else than rows of brick numbers, `line` and `column` start at 1
+    LevelParse {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// SDL only reports errors as messages
+    Sdl(String),
+    /// An asset archive which can't be mounted, or a file in it which can't be read
+    Archive(ArchiveError),
+    /// A font, sound, manifest or other asset file which doesn't follow its format
+    InvalidAsset {
+        path: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Loading an entry of an asset manifest failed, `kind` is e.g. "Texture"
+    ManifestEntry {
+        kind: &'static str,
+        name: String,
+        source: Box<Error>,
+    },
+    /// Anything else, e.g. the GPU not supporting what's asked of it
+    Other(String),
+}
+
+/// The stage a GLSL source file is compiled for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+}
+
+impl Error {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        Error::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn invalid_asset(
+        path: &str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Error::InvalidAsset {
+            path: path.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub fn in_entry(self, kind: &'static str, name: &str) -> Self {
+        Error::ManifestEntry {
+            kind,
+            name: name.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::Image {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path, source),
+            Error::Image { path: None, source } => write!(f, "{}", source),
+            Error::ShaderCompile { stage, path, log } => {
+                write!(f, "{}: {} shader failed to compile:\n{}", path, stage, log)
+            }
+            Error::ShaderLink { log } => write!(f, "Shader program failed to link:\n{}", log),
+            Error::LevelParse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path, line, column, message),
+            Error::Sdl(message) => write!(f, "SDL: {}", message),
+            Error::Archive(source) => write!(f, "{}", source),
+            Error::InvalidAsset { path, source } => write!(f, "{}: {}", path, source),
+            Error::ManifestEntry { kind, name, source } => {
+                write!(f, "{} '{}': {}", kind, name, source)
+            }
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Archive(source) => Some(source),
+            Error::InvalidAsset { source, .. } => Some(source.as_ref()),
+            Error::ManifestEntry { source, .. } => Some(source.as_ref()),
+            Error::ShaderCompile { .. }
+            | Error::ShaderLink { .. }
+            | Error::LevelParse { .. }
+            | Error::Sdl(_)
+            | Error::Other(_) => None,
+        }
+    }
+}
+
+impl From<ArchiveError> for Error {
+    fn from(source: ArchiveError) -> Self {
+        Error::Archive(source)
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShaderStage::Vertex => "Vertex",
+            ShaderStage::Fragment => "Fragment",
+            ShaderStage::Geometry => "Geometry",
+        };
+
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,198 @@
+pub use sdl2::keyboard::{Keycode, Mod, Scancode};
+pub use sdl2::mouse::MouseButton;
+
+use sdl2::event::{Event as SdlEvent, WindowEvent};
+
+/// Engine level events, translated from the SDL events polled every frame.
+/// Events which the engine doesn't care about are dropped during translation.
+/// Mouse positions are in the logical coordinates of the game, not in window pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Quit,
+    KeyDown {
+        scancode: Option<Scancode>,
+        keycode: Option<Keycode>,
+        keymod: Mod,
+        repeat: bool,
+    },
+    KeyUp {
+        scancode: Option<Scancode>,
+        keycode: Option<Keycode>,
+        keymod: Mod,
+    },
+    TextInput {
+        text: String,
+    },
+    MouseMotion {
+        x: f32,
+        y: f32,
+        xrel: f32,
+        yrel: f32,
+    },
+    MouseButtonDown {
+        button: MouseButton,
+        clicks: u8,
+        x: f32,
+        y: f32,
+    },
+    MouseButtonUp {
+        button: MouseButton,
+        clicks: u8,
+        x: f32,
+        y: f32,
+    },
+    MouseWheel {
+        x: i32,
+        y: i32,
+    },
+    Resized {
+        width: u32,
+        height: u32,
+    },
+    FocusGained,
+    FocusLost,
+    Minimized,
+    Restored,
+}
+
+impl Event {
+    pub fn from_sdl(event: SdlEvent) -> Option<Self> {
+        let event = match event {
+            SdlEvent::Quit { .. } => Event::Quit,
+            SdlEvent::KeyDown {
+                scancode,
+                keycode,
+                keymod,
+                repeat,
+                ..
+            } => Event::KeyDown {
+                scancode,
+                keycode,
+                keymod,
+                repeat,
+            },
+            SdlEvent::KeyUp {
+                scancode,
+                keycode,
+                keymod,
+                ..
+            } => Event::KeyUp {
+                scancode,
+                keycode,
+                keymod,
+            },
+            SdlEvent::TextInput { text, .. } => Event::TextInput { text },
+            SdlEvent::MouseMotion {
+                x, y, xrel, yrel, ..
+            } => Event::MouseMotion {
+                x: x as f32,
+                y: y as f32,
+                xrel: xrel as f32,
+                yrel: yrel as f32,
+            },
+            SdlEvent::MouseButtonDown {
+                mouse_btn,
+                clicks,
+                x,
+                y,
+                ..
+            } => Event::MouseButtonDown {
+                button: mouse_btn,
+                clicks,
+                x: x as f32,
+                y: y as f32,
+            },
+            SdlEvent::MouseButtonUp {
+                mouse_btn,
+                clicks,
+                x,
+                y,
+                ..
+            } => Event::MouseButtonUp {
+                button: mouse_btn,
+                clicks,
+                x: x as f32,
+                y: y as f32,
+            },
+            SdlEvent::MouseWheel { x, y, .. } => Event::MouseWheel { x, y },
+            SdlEvent::Window { win_event, .. } => match win_event {
+                WindowEvent::SizeChanged(width, height) => Event::Resized {
+                    width: width.max(0) as u32,
+                    height: height.max(0) as u32,
+                },
+                WindowEvent::FocusGained => Event::FocusGained,
+                WindowEvent::FocusLost => Event::FocusLost,
+                WindowEvent::Minimized => Event::Minimized,
+                WindowEvent::Restored => Event::Restored,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(event)
+    }
+
+    /// Rewrites mouse positions with `position` and relative mouse movement with `delta`
+    pub fn map_mouse<P, D>(self, position: P, delta: D) -> Self
+    where
+        P: Fn(glam::Vec2) -> glam::Vec2,
+        D: Fn(glam::Vec2) -> glam::Vec2,
+    {
+        match self {
+            Event::MouseMotion { x, y, xrel, yrel } => {
+                let p = position(glam::vec2(x, y));
+                let d = delta(glam::vec2(xrel, yrel));
+
+                Event::MouseMotion {
+                    x: p.x,
+                    y: p.y,
+                    xrel: d.x,
+                    yrel: d.y,
+                }
+            }
+            Event::MouseButtonDown {
+                button,
+                clicks,
+                x,
+                y,
+            } => {
+                let p = position(glam::vec2(x, y));
+
+                Event::MouseButtonDown {
+                    button,
+                    clicks,
+                    x: p.x,
+                    y: p.y,
+                }
+            }
+            Event::MouseButtonUp {
+                button,
+                clicks,
+                x,
+                y,
+            } => {
+                let p = position(glam::vec2(x, y));
+
+                Event::MouseButtonUp {
+                    button,
+                    clicks,
+                    x: p.x,
+                    y: p.y,
+                }
+            }
+            event => event,
+        }
+    }
+
+    /// True when this is the first key down event of the given scancode, key repeats are ignored
+    pub fn is_key_pressed(&self, key: Scancode) -> bool {
+        matches!(self, Event::KeyDown { scancode: Some(s), repeat: false, .. } if *s == key)
+    }
+}
+
+/// Returned by the game after handling the events of a frame to tell the loop whether to keep going
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopControl {
+    Continue,
+    Quit,
+}
@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+/// Sleeping is only as precise as the OS scheduler, so the last stretch before
+/// the deadline is spent spinning instead
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// How the swap of the back buffer is synchronized with the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Waits for the vertical blank, no tearing
+    VSync,
+    /// Waits for the vertical blank unless the frame is late, then it swaps immediately.
+    /// Falls back to `VSync` when the driver doesn't support it
+    AdaptiveVSync,
+    /// Swaps as soon as the frame is ready
+    Uncapped,
+}
+
+/// Caps the frame rate by sleeping only for what is left of the frame budget
+pub struct FramePacer {
+    frame_budget: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl FramePacer {
+    /// `max_fps` of 0 means the frame rate is unlimited
+    pub fn new(max_fps: u32) -> Self {
+        let mut pacer = Self {
+            frame_budget: None,
+            deadline: None,
+        };
+        pacer.set_max_fps(max_fps);
+
+        pacer
+    }
+
+    /// `max_fps` of 0 means the frame rate is unlimited
+    pub fn set_max_fps(&mut self, max_fps: u32) {
+        self.frame_budget = match max_fps {
+            0 => None,
+            fps => Some(Duration::from_secs_f64(1.0 / fps as f64)),
+        };
+        self.deadline = None;
+    }
+
+    /// Blocks until the current frame has used up its budget. Deadlines are advanced by a fixed
+    /// budget so that small oversleeps don't accumulate, unless the frame was so late that the
+    /// pacer would otherwise try to catch up with a burst of frames.
+    pub fn wait(&mut self) {
+        let budget = match self.frame_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+
+        let deadline = self.frame_deadline(budget, Instant::now());
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining > SPIN_THRESHOLD {
+            std::thread::sleep(remaining - SPIN_THRESHOLD);
+        }
+
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+
+        self.deadline = Some(deadline + budget);
+    }
+
+    /// When the current frame ends, a frame more than a whole budget late starts over from `now`
+    fn frame_deadline(&self, budget: Duration, now: Instant) -> Instant {
+        match self.deadline {
+            Some(deadline) if deadline + budget > now => deadline,
+            _ => now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn max_fps_sets_the_budget() {
+        assert_eq!(FramePacer::new(0).frame_budget, None);
+        assert_eq!(FramePacer::new(100).frame_budget, Some(BUDGET));
+    }
+
+    #[test]
+    fn first_frame_does_not_wait() {
+        let pacer = FramePacer::new(100);
+        let now = Instant::now();
+
+        assert_eq!(pacer.frame_deadline(BUDGET, now), now);
+    }
+
+    #[test]
+    fn waits_for_what_is_left_of_the_budget() {
+        let mut pacer = FramePacer::new(100);
+        let start = Instant::now();
+        pacer.deadline = Some(start + BUDGET);
+
+        let now = start + Duration::from_millis(3);
+        let remaining = pacer.frame_deadline(BUDGET, now) - now;
+
+        assert_eq!(remaining, Duration::from_millis(7));
+    }
+
+    #[test]
+    fn slightly_late_frames_keep_the_schedule() {
+        let mut pacer = FramePacer::new(100);
+        let start = Instant::now();
+        pacer.deadline = Some(start + BUDGET);
+
+        // The deadline already passed, the next one is still a budget after it
+        let now = start + Duration::from_millis(15);
+        let deadline = pacer.frame_deadline(BUDGET, now);
+
+        assert_eq!(deadline, start + BUDGET);
+        assert_eq!(deadline.saturating_duration_since(now), Duration::ZERO);
+    }
+
+    #[test]
+    fn very_late_frames_reset_the_schedule() {
+        let mut pacer = FramePacer::new(100);
+        let start = Instant::now();
+        pacer.deadline = Some(start + BUDGET);
+
+        let now = start + Duration::from_millis(25);
+
+        assert_eq!(pacer.frame_deadline(BUDGET, now), now);
+    }
+
+    #[test]
+    fn changing_the_limit_resets_the_schedule() {
+        let mut pacer = FramePacer::new(100);
+        pacer.deadline = Some(Instant::now() + BUDGET);
+        pacer.set_max_fps(0);
+
+        assert_eq!(pacer.deadline, None);
+        assert_eq!(pacer.frame_budget, None);
+    }
+}
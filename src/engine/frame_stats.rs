@@ -0,0 +1,65 @@
+use std::{collections::VecDeque, time::Instant};
+
+/// Durations of the most recent frames, for frame rate counters and frame time graphs
+pub struct FrameStats {
+    /// In seconds, oldest first
+    frame_times: VecDeque<f32>,
+    capacity: usize,
+    last_frame: Option<Instant>,
+}
+
+impl FrameStats {
+    /// Keeps the durations of the last `capacity` frames
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            last_frame: None,
+        }
+    }
+
+    /// Records the time passed since the previous call, meant to be called once per rendered frame
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == self.capacity {
+                self.frame_times.pop_front();
+            }
+
+            self.frame_times.push_back((now - last_frame).as_secs_f32());
+        }
+
+        self.last_frame = Some(now);
+    }
+
+    /// Frame durations in seconds, oldest first
+    pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    /// Average frame duration in seconds
+    pub fn average(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    /// Longest frame duration in seconds
+    pub fn max(&self) -> f32 {
+        self.frame_times.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Frames per second averaged over the recorded frames
+    pub fn fps(&self) -> f32 {
+        let average = self.average();
+
+        if average > 0.0 {
+            1.0 / average
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    ffi::CStr,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use gl::types::*;
+
+/// Checks `glGetError` after the wrapped call when error checking is enabled and reports
+/// every error together with the call and where it was made. Has to be used in an `unsafe` block.
+macro_rules! gl_call {
+    ($call:expr) => {{
+        let result = $call;
+        $crate::engine::gl_debug::check_errors(stringify!($call), file!(), line!());
+        result
+    }};
+}
+
+/// How serious a message of the driver is, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl DebugSeverity {
+    fn from_gl(severity: GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        }
+    }
+}
+
+/// OpenGL debugging, slows rendering down so it's meant for development builds
+#[derive(Debug, Clone, Copy)]
+pub struct GlDebug {
+    /// Driver messages less severe than this are dropped
+    pub min_severity: DebugSeverity,
+    /// Whether calls wrapped in `gl_call!` check `glGetError`, which stalls the pipeline
+    pub check_errors: bool,
+}
+
+impl Default for GlDebug {
+    fn default() -> Self {
+        Self {
+            min_severity: DebugSeverity::Low,
+            check_errors: true,
+        }
+    }
+}
+
+static CHECK_ERRORS: AtomicBool = AtomicBool::new(false);
+static MIN_SEVERITY: AtomicU8 = AtomicU8::new(DebugSeverity::High as u8);
+
+/// Installs the message callback of `KHR_debug` and enables error checking, the GL functions
+/// have to be loaded. Without a debug context drivers may send few messages or none at all.
+pub fn install(debug: GlDebug) {
+    CHECK_ERRORS.store(debug.check_errors, Ordering::Relaxed);
+    MIN_SEVERITY.store(debug.min_severity as u8, Ordering::Relaxed);
+
+    if !gl::DebugMessageCallback::is_loaded() {
+        eprintln!("GL debug output isn't supported by the driver, only errors will be checked");
+        return;
+    }
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // Messages are sent from the offending call so a debugger breaking in the callback
+        // shows where it came from
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(message_callback), std::ptr::null());
+        gl::DebugMessageControl(
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            0,
+            std::ptr::null(),
+            gl::TRUE,
+        );
+    }
+}
+
+/// Reports every pending GL error, does nothing unless error checking was enabled by `install`
+pub fn check_errors(call: &str, file: &str, line: u32) {
+    if !CHECK_ERRORS.load(Ordering::Relaxed) {
+        return;
+    }
+
+    loop {
+        let error = unsafe { gl::GetError() };
+
+        if error == gl::NO_ERROR {
+            break;
+        }
+
+        eprintln!(
+            "GL error {} in {} at {}:{}",
+            error_name(error),
+            call,
+            file,
+            line
+        );
+    }
+}
+
+extern "system" fn message_callback(
+    source: GLenum,
+    kind: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut std::ffi::c_void,
+) {
+    let severity = DebugSeverity::from_gl(severity);
+
+    if (severity as u8) < MIN_SEVERITY.load(Ordering::Relaxed) || message.is_null() {
+        return;
+    }
+
+    let message = if length >= 0 {
+        let bytes = unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
+        String::from_utf8_lossy(bytes).into_owned()
+    } else {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    eprintln!(
+        "GL {:?} {} {} ({}): {}",
+        severity,
+        source_name(source),
+        type_name(kind),
+        id,
+        message.trim_end()
+    );
+}
+
+fn error_name(error: GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown error",
+    }
+}
+
+fn source_name(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(kind: GLenum) -> &'static str {
+    match kind {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        _ => "other",
+    }
+}
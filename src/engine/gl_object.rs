@@ -0,0 +1,74 @@
+use std::{marker::PhantomData, rc::Rc};
+
+use gl::types::*;
+
+/// A kind of GL object which is created and deleted with a `glGen*`/`glDelete*` pair
+pub trait GlKind {
+    fn create() -> GLuint;
+    fn delete(id: GLuint);
+}
+
+macro_rules! gl_kind {
+    ($kind:ident, $gen:ident, $delete:ident) => {
+        #[derive(Debug)]
+        pub enum $kind {}
+
+        impl GlKind for $kind {
+            fn create() -> GLuint {
+                let mut id = 0;
+
+                unsafe {
+                    gl_call!(gl::$gen(1, &mut id));
+                }
+
+                id
+            }
+
+            fn delete(id: GLuint) {
+                unsafe {
+                    gl_call!(gl::$delete(1, &id));
+                }
+            }
+        }
+    };
+}
+
+gl_kind!(TextureKind, GenTextures, DeleteTextures);
+gl_kind!(BufferKind, GenBuffers, DeleteBuffers);
+gl_kind!(VertexArrayKind, GenVertexArrays, DeleteVertexArrays);
+gl_kind!(FramebufferKind, GenFramebuffers, DeleteFramebuffers);
+gl_kind!(RenderbufferKind, GenRenderbuffers, DeleteRenderbuffers);
+
+/// Owns a GL object, which is deleted when this is dropped. The GL context has to outlive it.
+#[derive(Debug)]
+pub struct GlObject<K: GlKind> {
+    id: GLuint,
+    kind: PhantomData<K>,
+}
+
+impl<K: GlKind> GlObject<K> {
+    pub fn new() -> Self {
+        Self {
+            id: K::create(),
+            kind: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl<K: GlKind> Drop for GlObject<K> {
+    fn drop(&mut self) {
+        K::delete(self.id);
+    }
+}
+
+/// A GL object shared by cheap clones, it's deleted together with the last clone
+pub type SharedGlObject<K> = Rc<GlObject<K>>;
+
+pub type Buffer = GlObject<BufferKind>;
+pub type VertexArray = GlObject<VertexArrayKind>;
+pub type Framebuffer = GlObject<FramebufferKind>;
+pub type Renderbuffer = GlObject<RenderbufferKind>;
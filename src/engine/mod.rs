@@ -1,9 +1,38 @@
+pub mod animation;
+pub mod archive;
+mod audio;
+pub mod camera;
+pub mod display;
+pub mod error;
+pub mod event;
+mod frame_pacer;
+mod frame_stats;
+#[macro_use]
+pub mod gl_debug;
+mod gl_object;
+mod particles;
+mod post_processor;
 mod renderer;
 mod resource_manager;
 mod sprite_renderer;
+pub mod vfs;
+pub mod viewport;
 
-pub use resource_manager::texture::Texture;
+pub use resource_manager::atlas::TextureRegion;
+pub use resource_manager::font::{Align, Font, TextStyle};
 
-pub use renderer::Renderer;
-pub use resource_manager::ResourceManager;
-pub use sprite_renderer::SpriteRenderer;
+pub use animation::{AnimationClip, AnimationPlayer, PlaybackMode};
+pub use audio::{Audio, SoundParams};
+pub use camera::Camera2D;
+pub use error::Error;
+pub use event::{Event, LoopControl, Scancode};
+pub use frame_pacer::PresentMode;
+pub use frame_stats::FrameStats;
+pub use gl_debug::GlDebug;
+pub use particles::{EmitterConfig, ParticleEmitter};
+pub use post_processor::{Effect, PostProcessor};
+pub use renderer::{GameLoop, Renderer};
+pub use resource_manager::{loader::LoadProgress, ResourceManager};
+pub use sprite_renderer::{BlendMode, Drawable, Layer, SpriteRenderer};
+pub use vfs::Vfs;
+pub use viewport::ScalingPolicy;
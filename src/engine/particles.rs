@@ -0,0 +1,388 @@
+use rand::Rng;
+
+use super::{
+    resource_manager::atlas::TextureRegion,
+    sprite_renderer::{BlendMode, Drawable, Layer},
+    SpriteRenderer,
+};
+
+/// Describes how an emitter spawns its particles and how they change over their life.
+/// Ranges are `(min, max)` and picked uniformly for every particle.
+#[derive(Debug, Clone, Copy)]
+pub struct EmitterConfig {
+    /// Particles spawned per second while emitting
+    pub spawn_rate: f32,
+    /// Seconds a particle stays alive
+    pub lifetime: (f32, f32),
+    /// Initial speed in pixels per second
+    pub speed: (f32, f32),
+    /// Main direction particles are launched in, doesn't have to be normalized
+    pub direction: glam::Vec2,
+    /// Total angle in degrees around `direction` particles are spread over, 360 emits in every
+    /// direction. Clamped to 0..=360
+    pub spread: f32,
+    /// Fraction of the velocity of the emitter added to new particles
+    pub inherit_velocity: f32,
+    /// Largest distance from the origin a particle is spawned at
+    pub position_jitter: f32,
+    /// Constant acceleration such as gravity, in pixels per second squared
+    pub acceleration: glam::Vec2,
+    pub color_start: glam::Vec4,
+    pub color_end: glam::Vec4,
+    pub size_start: f32,
+    pub size_end: f32,
+    pub blend: BlendMode,
+    pub layer: Layer,
+    pub sort_key: i32,
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 0.0,
+            lifetime: (1.0, 1.0),
+            speed: (0.0, 0.0),
+            direction: glam::vec2(0.0, -1.0),
+            spread: 360.0,
+            inherit_velocity: 0.0,
+            position_jitter: 0.0,
+            acceleration: glam::Vec2::ZERO,
+            color_start: glam::Vec4::ONE,
+            color_end: glam::vec4(1.0, 1.0, 1.0, 0.0),
+            size_start: 10.0,
+            size_end: 0.0,
+            blend: BlendMode::Alpha,
+            layer: Layer::Effects,
+            sort_key: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Particle {
+    position: glam::Vec2,
+    velocity: glam::Vec2,
+    /// Multiplied with the colors of the config, lets bursts tint their particles
+    tint: glam::Vec4,
+    /// Seconds left to live
+    life: f32,
+    lifetime: f32,
+}
+
+/// Spawns, simulates and draws particles out of a pool allocated up front. When the pool is
+/// full no new particles are spawned until old ones die.
+pub struct ParticleEmitter {
+    config: EmitterConfig,
+    sprite: TextureRegion,
+    particles: Particles,
+}
+
+impl ParticleEmitter {
+    pub fn new(config: EmitterConfig, sprite: TextureRegion, capacity: usize) -> Self {
+        Self {
+            config,
+            sprite,
+            particles: Particles::new(capacity),
+        }
+    }
+
+    /// Kills every particle
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Advances the particles by `delta_time` seconds and, when `emitting`, spawns new ones
+    /// at `origin` according to the spawn rate. `origin_velocity` is the velocity of whatever
+    /// the emitter is attached to.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        origin: glam::Vec2,
+        origin_velocity: glam::Vec2,
+        emitting: bool,
+    ) {
+        self.particles.simulate(&self.config, delta_time);
+
+        let count = if emitting {
+            self.particles.due(self.config.spawn_rate, delta_time)
+        } else {
+            self.particles.spawn_accumulator = 0.0;
+            0
+        };
+
+        for _ in 0..count {
+            self.particles
+                .spawn(&self.config, origin, origin_velocity, glam::Vec4::ONE);
+        }
+    }
+
+    /// Spawns `count` particles at once, `tint` is multiplied with the colors of the config
+    pub fn burst(&mut self, count: usize, origin: glam::Vec2, tint: glam::Vec4) {
+        for _ in 0..count {
+            self.particles
+                .spawn(&self.config, origin, glam::Vec2::ZERO, tint);
+        }
+    }
+}
+
+/// The pool of an emitter. Alive particles are kept at the front, a dying particle is swapped
+/// with the last alive one so nothing is allocated or shifted while the game runs.
+struct Particles {
+    pool: Vec<Particle>,
+    alive: usize,
+    /// Fractional particles carried over between updates so low spawn rates still emit
+    spawn_accumulator: f32,
+}
+
+impl Particles {
+    fn new(capacity: usize) -> Self {
+        Self {
+            pool: vec![Particle::default(); capacity],
+            alive: 0,
+            spawn_accumulator: 0.0,
+        }
+    }
+
+    fn alive(&self) -> &[Particle] {
+        &self.pool[..self.alive]
+    }
+
+    fn clear(&mut self) {
+        self.alive = 0;
+        self.spawn_accumulator = 0.0;
+    }
+
+    /// Ages and moves every alive particle, removing those whose life is over
+    fn simulate(&mut self, config: &EmitterConfig, delta_time: f32) {
+        let mut index = 0;
+
+        while index < self.alive {
+            let particle = &mut self.pool[index];
+            particle.life -= delta_time;
+
+            if particle.life <= 0.0 {
+                self.alive -= 1;
+                self.pool.swap(index, self.alive);
+                continue;
+            }
+
+            particle.velocity += config.acceleration * delta_time;
+            particle.position += particle.velocity * delta_time;
+            index += 1;
+        }
+    }
+
+    /// How many particles `spawn_rate` asks for over `delta_time`, keeping the fraction
+    /// for the next update
+    fn due(&mut self, spawn_rate: f32, delta_time: f32) -> usize {
+        self.spawn_accumulator += spawn_rate * delta_time;
+        let count = self.spawn_accumulator as usize;
+        self.spawn_accumulator -= count as f32;
+
+        count
+    }
+
+    fn spawn(
+        &mut self,
+        config: &EmitterConfig,
+        origin: glam::Vec2,
+        origin_velocity: glam::Vec2,
+        tint: glam::Vec4,
+    ) {
+        if self.alive == self.pool.len() {
+            return;
+        }
+
+        let mut rng = rand::thread_rng();
+
+        // Also catches a NaN spread, which would leave no angle to pick from
+        let spread = if config.spread > 0.0 {
+            config.spread.min(360.0)
+        } else {
+            0.0
+        };
+        let half_spread = spread.to_radians() / 2.0;
+        let angle = config.direction.y.atan2(config.direction.x)
+            + rng.gen_range(-half_spread..=half_spread);
+        let speed = random_in(&mut rng, config.speed);
+        let lifetime = random_in(&mut rng, config.lifetime).max(f32::EPSILON);
+
+        let jitter = glam::vec2(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0))
+            * config.position_jitter;
+
+        self.pool[self.alive] = Particle {
+            position: origin + jitter,
+            velocity: glam::vec2(angle.cos(), angle.sin()) * speed
+                + origin_velocity * config.inherit_velocity,
+            tint,
+            life: lifetime,
+            lifetime,
+        };
+        self.alive += 1;
+    }
+}
+
+impl Drawable for ParticleEmitter {
+    fn layer(&self) -> Layer {
+        self.config.layer
+    }
+
+    fn sort_key(&self) -> i32 {
+        self.config.sort_key
+    }
+
+    /// Queues every alive particle, centered on its position
+    fn draw(&self, sprite_renderer: &mut SpriteRenderer, _alpha: f32) {
+        for particle in self.particles.alive() {
+            let progress = 1.0 - particle.life / particle.lifetime;
+            let size =
+                self.config.size_start + (self.config.size_end - self.config.size_start) * progress;
+            let color = self
+                .config
+                .color_start
+                .lerp(self.config.color_end, progress)
+                * particle.tint;
+
+            sprite_renderer.draw_region_blended(
+                &self.sprite,
+                particle.position - size / 2.0,
+                glam::vec2(size, size),
+                0.0,
+                color,
+                self.config.blend,
+            );
+        }
+    }
+}
+
+fn random_in(rng: &mut impl Rng, (min, max): (f32, f32)) -> f32 {
+    if max > min {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> EmitterConfig {
+        EmitterConfig {
+            lifetime: (1.0, 1.0),
+            speed: (10.0, 10.0),
+            direction: glam::vec2(1.0, 0.0),
+            spread: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn spawns_no_more_than_the_capacity() {
+        let mut particles = Particles::new(3);
+
+        for _ in 0..5 {
+            particles.spawn(
+                &config(),
+                glam::Vec2::ZERO,
+                glam::Vec2::ZERO,
+                glam::Vec4::ONE,
+            );
+        }
+
+        assert_eq!(particles.alive().len(), 3);
+    }
+
+    #[test]
+    fn dead_particles_make_room_at_the_end() {
+        let mut particles = Particles::new(4);
+
+        for (x, lifetime) in [(0.0, 1.0), (1.0, 0.5), (2.0, 1.0), (3.0, 0.5)] {
+            let config = EmitterConfig {
+                lifetime: (lifetime, lifetime),
+                ..config()
+            };
+            particles.spawn(
+                &config,
+                glam::vec2(x, 0.0),
+                glam::Vec2::ZERO,
+                glam::Vec4::ONE,
+            );
+        }
+
+        particles.simulate(&config(), 0.75);
+
+        // The particle at 1 died and was replaced by the last one, which also died
+        let xs: Vec<f32> = particles
+            .alive()
+            .iter()
+            .map(|particle| particle.position.x)
+            .collect();
+        assert_eq!(xs, [7.5, 9.5]);
+        assert!(particles.pool[2..]
+            .iter()
+            .all(|particle| particle.life <= 0.0));
+    }
+
+    #[test]
+    fn particles_move_and_accelerate() {
+        let mut particles = Particles::new(1);
+        let config = EmitterConfig {
+            acceleration: glam::vec2(0.0, 100.0),
+            ..config()
+        };
+        particles.spawn(
+            &config,
+            glam::Vec2::ZERO,
+            glam::vec2(50.0, 0.0),
+            glam::Vec4::ONE,
+        );
+        particles.simulate(&config, 0.5);
+
+        let particle = particles.alive()[0];
+        assert!(particle.velocity.abs_diff_eq(glam::vec2(10.0, 50.0), 1e-4));
+        assert!(particle.position.abs_diff_eq(glam::vec2(5.0, 25.0), 1e-4));
+    }
+
+    #[test]
+    fn fractional_spawn_rates_add_up() {
+        let mut particles = Particles::new(0);
+
+        // 2.5 particles per second over ten updates of 0.1 seconds
+        let counts: Vec<usize> = (0..10).map(|_| particles.due(2.5, 0.1)).collect();
+
+        assert_eq!(counts.iter().sum::<usize>(), 2);
+        assert_eq!(counts.iter().position(|&count| count == 1), Some(3));
+    }
+
+    #[test]
+    fn negative_or_nan_spread_emits_straight() {
+        let mut particles = Particles::new(2);
+
+        for spread in [-90.0, f32::NAN] {
+            let config = EmitterConfig { spread, ..config() };
+            particles.spawn(&config, glam::Vec2::ZERO, glam::Vec2::ZERO, glam::Vec4::ONE);
+        }
+
+        // Without any spread every particle goes straight along the direction
+        for particle in particles.alive() {
+            assert!(particle.velocity.abs_diff_eq(glam::vec2(10.0, 0.0), 1e-4));
+        }
+    }
+
+    #[test]
+    fn clear_kills_everything() {
+        let mut particles = Particles::new(2);
+        particles.spawn(
+            &config(),
+            glam::Vec2::ZERO,
+            glam::Vec2::ZERO,
+            glam::Vec4::ONE,
+        );
+        particles.due(0.5, 1.0);
+        particles.clear();
+
+        assert!(particles.alive().is_empty());
+        assert_eq!(particles.spawn_accumulator, 0.0);
+    }
+}
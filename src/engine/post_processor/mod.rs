@@ -0,0 +1,313 @@
+use gl::types::*;
+
+use super::error::Error;
+use super::gl_object::{Buffer, Framebuffer, GlObject, Renderbuffer, TextureKind, VertexArray};
+use super::resource_manager::{shader::Shader, uniform::Sampler};
+
+/// Effects the post-processing shader can apply to the whole scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Slightly blurred screen that wobbles around
+    Shake,
+    /// Flips the scene upside down and inverts its colors
+    Confuse,
+    /// Edge detection on a scene that swirls around
+    Chaos,
+}
+
+const EFFECTS: [Effect; 3] = [Effect::Shake, Effect::Confuse, Effect::Chaos];
+
+/// Renders the scene into an offscreen framebuffer and then draws that onto the screen
+/// through the post-processing shader. Use `begin_render` before drawing the scene,
+/// `end_render` after it and finally `render` to present the result.
+pub struct PostProcessor {
+    shader: Shader,
+    width: u32,
+    height: u32,
+    /// Multisampled framebuffer the scene is drawn into, `None` when multisampling is off
+    /// and the scene is drawn straight into `fbo`
+    msfbo: Option<(Framebuffer, Renderbuffer)>,
+    /// Framebuffer holding the resolved scene texture
+    fbo: Framebuffer,
+    texture: GlObject<TextureKind>,
+    quad_vao: VertexArray,
+    /// Only referenced by the vertex array, kept to be deleted with it
+    _quad_vbo: Buffer,
+    /// Whether each effect is on, indexed by `Effect`
+    enabled: [bool; 3],
+    saved_viewport: [GLint; 4],
+}
+
+impl PostProcessor {
+    /// `samples` is the number of MSAA samples, 0 or 1 disables multisampling
+    pub fn new(width: u32, height: u32, shader: Shader, samples: u32) -> Result<Self, Error> {
+        let multisampled = samples > 1;
+
+        // Objects created before a failure are deleted on the way out
+        let fbo = Framebuffer::new();
+        let texture = GlObject::<TextureKind>::new();
+        let mut msfbo = None;
+
+        unsafe {
+            if multisampled {
+                let framebuffer = Framebuffer::new();
+                let rbo = Renderbuffer::new();
+
+                gl_call!(gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.id()));
+                gl_call!(gl::BindRenderbuffer(gl::RENDERBUFFER, rbo.id()));
+                gl_call!(gl::RenderbufferStorageMultisample(
+                    gl::RENDERBUFFER,
+                    samples as GLsizei,
+                    gl::RGBA8,
+                    width as GLsizei,
+                    height as GLsizei,
+                ));
+                gl_call!(gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::RENDERBUFFER,
+                    rbo.id(),
+                ));
+
+                if gl_call!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER)) != gl::FRAMEBUFFER_COMPLETE
+                {
+                    gl_call!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+                    return Err(Error::Other(String::from(
+                        "Couldn't initialize the multisampled framebuffer.",
+                    )));
+                }
+
+                msfbo = Some((framebuffer, rbo));
+            }
+
+            gl_call!(gl::BindTexture(gl::TEXTURE_2D, texture.id()));
+            gl_call!(gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            ));
+            // The chaos effect moves texture coordinates outside of the texture, repeat makes it wrap around
+            gl_call!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::REPEAT as GLint
+            ));
+            gl_call!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::REPEAT as GLint
+            ));
+            gl_call!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_call!(gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as GLint
+            ));
+            gl_call!(gl::BindTexture(gl::TEXTURE_2D, 0));
+
+            gl_call!(gl::BindFramebuffer(gl::FRAMEBUFFER, fbo.id()));
+            gl_call!(gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture.id(),
+                0,
+            ));
+
+            if gl_call!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER)) != gl::FRAMEBUFFER_COMPLETE {
+                gl_call!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+                return Err(Error::Other(String::from(
+                    "Couldn't initialize the framebuffer.",
+                )));
+            }
+
+            gl_call!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+        }
+
+        let (quad_vao, quad_vbo) = create_screen_quad();
+
+        Ok(Self {
+            shader,
+            width,
+            height,
+            msfbo,
+            fbo,
+            texture,
+            quad_vao,
+            _quad_vbo: quad_vbo,
+            enabled: [false; 3],
+            saved_viewport: [0; 4],
+        })
+    }
+
+    /// Redirects all drawing into the offscreen framebuffer
+    pub fn begin_render(&mut self) {
+        unsafe {
+            gl_call!(gl::GetIntegerv(
+                gl::VIEWPORT,
+                self.saved_viewport.as_mut_ptr()
+            ));
+            gl_call!(gl::BindFramebuffer(
+                gl::FRAMEBUFFER,
+                self.scene_framebuffer()
+            ));
+            gl_call!(gl::Viewport(
+                0,
+                0,
+                self.width as GLsizei,
+                self.height as GLsizei
+            ));
+            gl_call!(gl::ClearColor(0.0, 0.0, 0.0, 1.0));
+            gl_call!(gl::Clear(gl::COLOR_BUFFER_BIT));
+        }
+    }
+
+    /// Resolves the multisampled scene into the scene texture and goes back to the default framebuffer
+    pub fn end_render(&mut self) {
+        unsafe {
+            if let Some((msfbo, _)) = &self.msfbo {
+                gl_call!(gl::BindFramebuffer(gl::READ_FRAMEBUFFER, msfbo.id()));
+                gl_call!(gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo.id()));
+                gl_call!(gl::BlitFramebuffer(
+                    0,
+                    0,
+                    self.width as GLint,
+                    self.height as GLint,
+                    0,
+                    0,
+                    self.width as GLint,
+                    self.height as GLint,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                ));
+            }
+
+            gl_call!(gl::BindFramebuffer(gl::FRAMEBUFFER, 0));
+
+            let [x, y, width, height] = self.saved_viewport;
+            gl_call!(gl::Viewport(x, y, width, height));
+        }
+    }
+
+    /// Draws the processed scene to the screen, `time` drives the animated effects
+    pub fn render(&self, time: f32) {
+        self.shader.to_use();
+        // Set every frame as the program may have been swapped by a hot-reload
+        self.set_uniforms(time);
+
+        unsafe {
+            gl_call!(gl::ActiveTexture(gl::TEXTURE0));
+            gl_call!(gl::BindTexture(gl::TEXTURE_2D, self.texture.id()));
+            gl_call!(gl::BindVertexArray(self.quad_vao.id()));
+            gl_call!(gl::DrawArrays(gl::TRIANGLES, 0, 6));
+            gl_call!(gl::BindVertexArray(0));
+        }
+    }
+
+    pub fn set_effect(&mut self, effect: Effect, enabled: bool) {
+        self.enabled[effect as usize] = enabled;
+    }
+
+    pub fn is_active(&self, effect: Effect) -> bool {
+        self.enabled[effect as usize]
+    }
+
+    /// Where the scene is drawn to, the multisampled framebuffer if there is one
+    fn scene_framebuffer(&self) -> GLuint {
+        match &self.msfbo {
+            Some((msfbo, _)) => msfbo.id(),
+            None => self.fbo.id(),
+        }
+    }
+
+    fn set_uniforms(&self, time: f32) {
+        let offset = 1.0 / 300.0;
+        let offsets = [
+            glam::vec2(-offset, offset),
+            glam::vec2(0.0, offset),
+            glam::vec2(offset, offset),
+            glam::vec2(-offset, 0.0),
+            glam::vec2(0.0, 0.0),
+            glam::vec2(offset, 0.0),
+            glam::vec2(-offset, -offset),
+            glam::vec2(0.0, -offset),
+            glam::vec2(offset, -offset),
+        ];
+        let edge_kernel: [i32; 9] = [-1, -1, -1, -1, 8, -1, -1, -1, -1];
+        let blur_kernel: [f32; 9] = [
+            1.0 / 16.0,
+            2.0 / 16.0,
+            1.0 / 16.0,
+            2.0 / 16.0,
+            4.0 / 16.0,
+            2.0 / 16.0,
+            1.0 / 16.0,
+            2.0 / 16.0,
+            1.0 / 16.0,
+        ];
+
+        self.shader.set("scene", &Sampler(0));
+        self.shader.set("offsets", &offsets);
+        self.shader.set("edge_kernel", &edge_kernel);
+        self.shader.set("blur_kernel", &blur_kernel);
+        self.shader.set("time", &time);
+
+        for effect in EFFECTS {
+            let name = match effect {
+                Effect::Shake => "shake",
+                Effect::Confuse => "confuse",
+                Effect::Chaos => "chaos",
+            };
+
+            self.shader.set(name, &self.is_active(effect));
+        }
+    }
+}
+
+/// Quad covering the whole screen in normalized device coordinates
+fn create_screen_quad() -> (VertexArray, Buffer) {
+    let vao = VertexArray::new();
+    let vbo = Buffer::new();
+
+    let vertices: [f32; 24] = [
+        // pos        // tex
+        -1.0, -1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 0.0, 1.0, -1.0, -1.0, 0.0, 0.0, 1.0,
+        -1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0,
+    ];
+
+    unsafe {
+        gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, vbo.id()));
+        gl_call!(gl::BufferData(
+            gl::ARRAY_BUFFER,
+            (std::mem::size_of::<f32>() * vertices.len()) as GLsizeiptr,
+            vertices.as_ptr() as *const _,
+            gl::STATIC_DRAW,
+        ));
+
+        gl_call!(gl::BindVertexArray(vao.id()));
+        gl_call!(gl::EnableVertexAttribArray(0));
+        gl_call!(gl::VertexAttribPointer(
+            0,
+            4,
+            gl::FLOAT,
+            gl::FALSE,
+            (4 * std::mem::size_of::<f32>()) as GLsizei,
+            std::ptr::null(),
+        ));
+
+        gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+        gl_call!(gl::BindVertexArray(0));
+    }
+
+    (vao, vbo)
+}
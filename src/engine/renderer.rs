@@ -1,134 +1,440 @@
-use sdl2::{video::GLProfile, EventPump};
+use std::time::{Duration, Instant};
+
+use sdl2::{
+    video::{GLContext, GLProfile, SwapInterval},
+    EventPump,
+};
+
+use super::display::{self, DisplayInfo, DisplayMode, WindowMode};
+use super::error::Error;
+use super::event::{Event, LoopControl, Scancode};
+use super::frame_pacer::{FramePacer, PresentMode};
+use super::gl_debug::{self, GlDebug};
+use super::viewport::{ScalingPolicy, Viewport};
+
+/// Caps how many ticks are simulated for a single frame, when a frame takes longer than that
+/// the simulation slows down instead of trying to catch up forever (the so called spiral of death)
+const MAX_UPDATES_PER_FRAME: u32 = 8;
+/// Toggles fullscreen while the game is running
+const FULLSCREEN_TOGGLE_KEY: Scancode = Scancode::F11;
+
+/// Implemented by whatever the renderer drives, game logic runs in `update` at a fixed
+/// tick rate while `draw` runs once per rendered frame.
+pub trait GameLoop {
+    /// Receives every event polled during the frame, in order, before any update of that frame.
+    /// By default the loop quits when the window is asked to close.
+    fn process_events(&mut self, events: &[Event]) -> LoopControl {
+        if events.contains(&Event::Quit) {
+            LoopControl::Quit
+        } else {
+            LoopControl::Continue
+        }
+    }
+
+    /// Advances the simulation by exactly `delta_time` seconds.
+    fn update(&mut self, event_pump: &EventPump, delta_time: f32);
+
+    /// Draws the current frame, `alpha` is how far (0.0..1.0) the renderer is between the
+    /// previous and the current simulation tick and should be used to interpolate positions.
+    fn draw(&mut self, alpha: f32);
+}
 
 pub struct Renderer {
-    timer: sdl2::TimerSubsystem,
+    sdl_context: sdl2::Sdl,
     window: sdl2::video::Window,
+    gl_context: Option<GLContext>,
+    gl_debug: Option<GlDebug>,
     event_pump: EventPump,
-    max_fps: u32,
+    frame_pacer: FramePacer,
+    present_mode: PresentMode,
+    tick_rate: u32,
     buffer_color: (u8, u8, u8),
+    scaling_policy: ScalingPolicy,
+    viewport: Viewport,
+    window_mode: WindowMode,
+    fullscreen_mode: WindowMode,
 }
 
 impl Renderer {
     /// Creates the window, initializes OpenGL context, and start the renderer
-    /// by default the created window will cap the max fps to 60
-    pub fn build(title: &str, width: u32, height: u32) -> Result<Self, String> {
-        let sdl_context = match sdl2::init() {
-            Ok(sdl) => sdl,
-            Err(e) => return Err(e),
-        };
-
-        let video_subsystem = match sdl_context.video() {
-            Ok(v) => v,
-            Err(e) => return Err(e),
-        };
+    /// by default the created window will cap the max fps to 60 and run game logic at 120 ticks per second.
+    /// `width` and `height` are also the logical resolution the game works in, no matter the window size
+    pub fn build(title: &str, width: u32, height: u32) -> Result<Self, Error> {
+        let sdl_context = sdl2::init().map_err(Error::Sdl)?;
+        let video_subsystem = sdl_context.video().map_err(Error::Sdl)?;
 
         let gl_attr = video_subsystem.gl_attr();
         gl_attr.set_context_profile(GLProfile::Core);
         gl_attr.set_context_version(3, 3);
 
-        let window = match video_subsystem
+        let window = video_subsystem
             .window(title, width, height)
             .opengl()
             .resizable()
+            .allow_highdpi()
             .build()
-        {
-            Ok(w) => w,
-            Err(_) => return Err(String::from("Couldn't create a window instance.")),
-        };
+            .map_err(|e| Error::Sdl(format!("Couldn't create a window instance: {}", e)))?;
 
         debug_assert_eq!(gl_attr.context_profile(), GLProfile::Core);
         debug_assert_eq!(gl_attr.context_version(), (3, 3));
 
-        let timer = sdl_context.timer()?;
-        let event_pump = sdl_context.event_pump()?;
+        let event_pump = sdl_context.event_pump().map_err(Error::Sdl)?;
+        let scaling_policy = ScalingPolicy::Letterbox;
+        let (drawable_width, drawable_height) = window.drawable_size();
+        let viewport = Viewport::compute(
+            scaling_policy,
+            width,
+            height,
+            drawable_width,
+            drawable_height,
+        );
 
         Ok(Self {
-            timer,
+            sdl_context,
             window,
+            gl_context: None,
+            gl_debug: None,
             event_pump,
-            max_fps: 60,
+            frame_pacer: FramePacer::new(60),
+            present_mode: PresentMode::Uncapped,
+            tick_rate: 120,
             buffer_color: (0, 0, 0),
+            scaling_policy,
+            viewport,
+            window_mode: WindowMode::Windowed,
+            fullscreen_mode: WindowMode::Borderless,
         })
     }
 
-    pub fn window(&self) -> &sdl2::video::Window {
-        &self.window
+    /// The SDL context, to initialize other subsystems such as audio
+    pub fn sdl(&self) -> &sdl2::Sdl {
+        &self.sdl_context
+    }
+
+    /// Requests a debug context with driver messages and error checks, `None` turns debugging off.
+    /// Only takes effect if called before `create_gl_context`
+    pub fn set_gl_debug(&mut self, debug: Option<GlDebug>) {
+        self.gl_debug = debug;
+    }
+
+    /// Creates the OpenGL context and loads the GL functions, nothing can be rendered before that
+    pub fn create_gl_context(&mut self) -> Result<(), Error> {
+        let video_subsystem = self.window.subsystem();
+
+        if self.gl_debug.is_some() {
+            video_subsystem.gl_attr().set_context_flags().debug().set();
+        }
+
+        let gl_context = self.window.gl_create_context().map_err(Error::Sdl)?;
+        // At the end of the day this defines the correct GL functions based on which OS we're compiling for.
+        gl::load_with(|name| video_subsystem.gl_get_proc_address(name) as *const _);
+
+        if let Some(debug) = self.gl_debug {
+            gl_debug::install(debug);
+        }
+
+        self.gl_context = Some(gl_context);
+        self.update_viewport();
+
+        Ok(())
+    }
+
+    pub fn set_scaling_policy(&mut self, policy: ScalingPolicy) {
+        self.scaling_policy = policy;
+        self.update_viewport();
+    }
+
+    pub fn set_window_mode(&mut self, mode: WindowMode) -> Result<(), Error> {
+        self.window
+            .set_fullscreen(mode.to_sdl())
+            .map_err(Error::Sdl)?;
+        self.window_mode = mode;
+
+        if mode != WindowMode::Windowed {
+            self.fullscreen_mode = mode;
+        }
+
+        self.update_viewport();
+
+        Ok(())
+    }
+
+    /// Switches between windowed mode and the fullscreen mode that was used last (borderless by default)
+    pub fn toggle_fullscreen(&mut self) -> Result<(), Error> {
+        if self.window_mode == WindowMode::Windowed {
+            self.set_window_mode(self.fullscreen_mode)
+        } else {
+            self.set_window_mode(WindowMode::Windowed)
+        }
+    }
+
+    /// Every monitor currently connected
+    pub fn displays(&self) -> Result<Vec<DisplayInfo>, Error> {
+        display::displays(self.window.subsystem())
+    }
+
+    /// Resolutions and refresh rates supported by the given monitor
+    pub fn display_modes(&self, display_index: i32) -> Result<Vec<DisplayMode>, Error> {
+        display::display_modes(self.window.subsystem(), display_index)
+    }
+
+    /// Index of the monitor the window is currently on
+    pub fn current_display(&self) -> Result<i32, Error> {
+        self.window.display_index().map_err(Error::Sdl)
+    }
+
+    /// Picks the resolution and refresh rate used in exclusive fullscreen
+    pub fn set_display_mode(&mut self, mode: DisplayMode) -> Result<(), Error> {
+        self.window
+            .set_display_mode(Some(mode.to_sdl()))
+            .map_err(Error::Sdl)?;
+        self.update_viewport();
+
+        Ok(())
     }
 
     pub fn set_color(&mut self, r: u8, g: u8, b: u8) {
         self.buffer_color = (r, g, b);
     }
 
+    /// Caps the frame rate, 0 means unlimited. The cap is applied on top of the present mode,
+    /// so with VSync the effective frame rate is the lower of the two
     pub fn set_max_fps(&mut self, fps: u32) {
-        if fps == 0 {
-            panic!("FPS can't be negative")
+        self.frame_pacer.set_max_fps(fps);
+    }
+
+    /// Takes effect once the game loop starts, as it needs the OpenGL context to exist
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        self.present_mode = mode;
+    }
+
+    /// Sets how many times per second `GameLoop::update` is called, independent of the frame rate
+    pub fn set_tick_rate(&mut self, ticks_per_second: u32) {
+        if ticks_per_second == 0 {
+            panic!("Tick rate has to be at least 1")
         }
 
-        self.max_fps = fps;
+        self.tick_rate = ticks_per_second;
     }
 
-    pub fn game_loop<T>(&mut self, l: &mut T)
+    pub fn game_loop<T>(&mut self, game: &mut T)
     where
-        T: FnMut(&sdl2::EventPump, f32),
+        T: GameLoop,
     {
         self.set_gl_commands();
 
-        let mut delta_time = 0f32;
-        let mut last_frame = 0f32;
+        let mut timestep = FixedTimestep::new(self.tick_rate, MAX_UPDATES_PER_FRAME);
+        let mut last_frame = Instant::now();
+        let mut events = Vec::new();
+
+        loop {
+            let current_frame = Instant::now();
+            let frame_time = current_frame - last_frame;
+            last_frame = current_frame;
 
-        'running: loop {
-            let current_frame = self.timer.ticks() as f32;
+            events.clear();
+            events.extend(self.event_pump.poll_iter().filter_map(Event::from_sdl));
 
-            for event in self.event_pump.poll_iter() {
-                match event {
-                    sdl2::event::Event::Quit { .. } => break 'running,
-                    _ => (),
+            if events
+                .iter()
+                .any(|e| e.is_key_pressed(FULLSCREEN_TOGGLE_KEY))
+            {
+                if let Err(e) = self.toggle_fullscreen() {
+                    eprintln!("Couldn't toggle fullscreen: {}", e);
                 }
             }
 
+            if events.iter().any(|e| matches!(e, Event::Resized { .. })) {
+                self.update_viewport();
+            }
+
+            for event in events.iter_mut() {
+                *event = self.to_logical_event(event.clone());
+            }
+
+            if game.process_events(&events) == LoopControl::Quit {
+                break;
+            }
+
+            for _ in 0..timestep.advance(frame_time) {
+                game.update(&self.event_pump, timestep.tick.as_secs_f32());
+            }
+
             Renderer::clear_buffer(
                 self.buffer_color.0,
                 self.buffer_color.1,
                 self.buffer_color.2,
             );
 
-            l(&self.event_pump, delta_time);
+            game.draw(timestep.alpha());
 
             self.window.gl_swap_window();
 
-            Renderer::limit_to_max_fps(self.max_fps);
+            self.frame_pacer.wait();
+        }
+    }
+
+    /// Recomputes the viewport from the current drawable size of the window, it's applied once
+    /// there is a GL context
+    fn update_viewport(&mut self) {
+        let (logical_width, logical_height) = self.viewport.logical_size();
+        let (drawable_width, drawable_height) = self.window.drawable_size();
 
-            delta_time = current_frame - last_frame;
-            last_frame = current_frame;
+        self.viewport = Viewport::compute(
+            self.scaling_policy,
+            logical_width,
+            logical_height,
+            drawable_width,
+            drawable_height,
+        );
+
+        if self.gl_context.is_some() {
+            self.viewport.apply();
         }
     }
 
+    /// Mouse events come in window coordinates, which on high-DPI displays differ from
+    /// the drawable pixels, so they're first scaled to drawable pixels and then to logical ones
+    fn to_logical_event(&self, event: Event) -> Event {
+        let (window_width, window_height) = self.window.size();
+        let (drawable_width, drawable_height) = self.window.drawable_size();
+        let dpi_scale = glam::vec2(
+            drawable_width as f32 / window_width as f32,
+            drawable_height as f32 / window_height as f32,
+        );
+
+        event.map_mouse(
+            |position| self.viewport.drawable_to_logical(position * dpi_scale),
+            |delta| self.viewport.drawable_delta_to_logical(delta * dpi_scale),
+        )
+    }
+
     // Only after the GL functions are initialized we can start doing preperatioins and etc
-    fn set_gl_commands(&self) {
+    fn set_gl_commands(&mut self) {
+        self.update_viewport();
+        self.apply_present_mode();
+
         unsafe {
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl_call!(gl::Enable(gl::BLEND));
+            gl_call!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA));
         }
     }
 
-    fn limit_to_max_fps(max_fps: u32) {
-        std::thread::sleep(::std::time::Duration::new(0, 1_000_000_000u32 / max_fps));
+    fn apply_present_mode(&self) {
+        let video_subsystem = self.window.subsystem();
+
+        let result = match self.present_mode {
+            PresentMode::VSync => video_subsystem.gl_set_swap_interval(SwapInterval::VSync),
+            PresentMode::AdaptiveVSync => video_subsystem
+                .gl_set_swap_interval(SwapInterval::LateSwapTearing)
+                .or_else(|_| video_subsystem.gl_set_swap_interval(SwapInterval::VSync)),
+            PresentMode::Uncapped => video_subsystem.gl_set_swap_interval(SwapInterval::Immediate),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Couldn't set the swap interval: {}", e);
+        }
     }
 
     fn clear_buffer(r: u8, g: u8, b: u8) {
         unsafe {
-            gl::ClearColor(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl_call!(gl::ClearColor(
+                r as f32 / 255.0,
+                g as f32 / 255.0,
+                b as f32 / 255.0,
+                1.0
+            ));
+            gl_call!(gl::Clear(gl::COLOR_BUFFER_BIT));
         }
     }
 }
 
 impl Drop for Renderer {
     fn drop(&mut self) {
+        // Without a context the GL functions were never loaded
+        if self.gl_context.is_none() {
+            return;
+        }
+
         unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_call!(gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0));
+            gl_call!(gl::BindVertexArray(0));
         }
     }
 }
+
+/// Splits the time frames take into fixed ticks, what's left over carries into the next frame
+#[derive(Debug)]
+struct FixedTimestep {
+    tick: Duration,
+    /// Longer frames are cut to this, so a single frame never runs more than the update limit
+    max_frame_time: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    fn new(tick_rate: u32, max_updates_per_frame: u32) -> Self {
+        let tick = Duration::from_secs_f64(1.0 / tick_rate as f64);
+
+        Self {
+            tick,
+            max_frame_time: tick * max_updates_per_frame,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Adds the time a frame took, returns how many ticks to update for
+    fn advance(&mut self, frame_time: Duration) -> u32 {
+        self.accumulator += frame_time.min(self.max_frame_time);
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+
+        ticks
+    }
+
+    /// How far the next tick is along, from 0.0 to 1.0, to interpolate drawing with
+    fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_whole_ticks_and_carries_the_rest() {
+        let mut timestep = FixedTimestep::new(100, 8);
+
+        assert_eq!(timestep.advance(Duration::from_millis(25)), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+
+        assert_eq!(timestep.advance(Duration::from_millis(5)), 1);
+        assert!(timestep.alpha() < 1e-4);
+    }
+
+    #[test]
+    fn short_frames_accumulate() {
+        let mut timestep = FixedTimestep::new(100, 8);
+
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 1);
+        assert!((timestep.alpha() - 0.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn long_frames_are_clamped_to_the_update_limit() {
+        let mut timestep = FixedTimestep::new(100, 8);
+
+        assert_eq!(timestep.advance(Duration::from_secs(5)), 8);
+        // Nothing of the skipped time is left to catch up on
+        assert!(timestep.alpha() < 1e-4);
+        assert_eq!(timestep.advance(Duration::from_millis(10)), 1);
+    }
+}
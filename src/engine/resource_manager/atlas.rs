@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+
+use image::RgbaImage;
+use serde::Deserialize;
+
+use super::texture::Texture;
+use crate::engine::error::Error;
+
+/// Border around every image in an atlas, filled with copies of the image's edge pixels
+/// so linear filtering never samples a neighbouring image
+const PADDING: u32 = 1;
+
+/// A texture packed by `AtlasBuilder` and the regions in it by name
+pub type AtlasPage = (Texture, HashMap<String, TextureRegion>);
+
+/// A rectangular part of a texture, what the sprite renderer actually draws
+#[derive(Debug, Clone)]
+pub struct TextureRegion {
+    texture: Texture,
+    /// Normalized texture coordinates, `xy` is the top left and `zw` the bottom right corner
+    uv: glam::Vec4,
+}
+
+impl TextureRegion {
+    pub fn new(texture: Texture, uv: glam::Vec4) -> Self {
+        Self { texture, uv }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn uv(&self) -> glam::Vec4 {
+        self.uv
+    }
+}
+
+/// A named rectangle inside of an image, in pixels
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Slice {
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Region description of a sprite sheet as stored in its JSON file
+#[derive(Debug, Deserialize)]
+pub struct SheetDescription {
+    pub regions: Vec<Slice>,
+}
+
+impl SheetDescription {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Slices a sheet made of equally sized cells, left to right and top to bottom,
+/// the slices are named `<name>/<index>`
+pub fn grid_slices(
+    name: &str,
+    image_width: u32,
+    image_height: u32,
+    cell_width: u32,
+    cell_height: u32,
+) -> Result<Vec<Slice>, Error> {
+    if cell_width == 0 || cell_height == 0 {
+        return Err(Error::Other(format!(
+            "Cells of {}x{} can't slice a sprite sheet",
+            cell_width, cell_height
+        )));
+    }
+
+    let columns = image_width / cell_width;
+    let rows = image_height / cell_height;
+
+    Ok((0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .enumerate()
+        .map(|(index, (row, column))| Slice {
+            name: format!("{}/{}", name, index),
+            x: column * cell_width,
+            y: row * cell_height,
+            width: cell_width,
+            height: cell_height,
+        })
+        .collect())
+}
+
+struct PendingImage {
+    image: RgbaImage,
+    slices: Vec<Slice>,
+}
+
+/// Packs images into as few textures as possible using simple shelf packing,
+/// images are sorted by height so each shelf wastes little space
+pub struct AtlasBuilder {
+    pending: Vec<PendingImage>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self { pending: vec![] }
+    }
+
+    /// Adds a whole image as a single region called `name`
+    pub fn add(&mut self, name: &str, image: RgbaImage) {
+        let slices = vec![Slice {
+            name: name.to_string(),
+            x: 0,
+            y: 0,
+            width: image.width(),
+            height: image.height(),
+        }];
+
+        self.pending.push(PendingImage { image, slices });
+    }
+
+    /// Adds an image which is cut into several regions once packed, `path` is the file the
+    /// slices were read from and is reported when one of them is outside of the image
+    pub fn add_sliced(
+        &mut self,
+        path: &str,
+        image: RgbaImage,
+        slices: Vec<Slice>,
+    ) -> Result<(), Error> {
+        for slice in slices.iter() {
+            let inside = |start: u32, size: u32, image_size: u32| {
+                start.checked_add(size).is_some_and(|end| end <= image_size)
+            };
+
+            if !inside(slice.x, slice.width, image.width())
+                || !inside(slice.y, slice.height, image.height())
+            {
+                return Err(Error::invalid_asset(
+                    path,
+                    format!(
+                        "region '{}' at {},{} of {}x{} is outside of its {}x{} image",
+                        slice.name,
+                        slice.x,
+                        slice.y,
+                        slice.width,
+                        slice.height,
+                        image.width(),
+                        image.height()
+                    ),
+                ));
+            }
+        }
+
+        self.pending.push(PendingImage { image, slices });
+
+        Ok(())
+    }
+
+    /// Packs and uploads every added image into textures of at most `max_size` squared,
+    /// returns the regions by name which keep their textures alive
+    pub fn build(&mut self, max_size: u32) -> Result<HashMap<String, TextureRegion>, Error> {
+        let mut regions = HashMap::new();
+
+        while let Some((_, page_regions)) = self.build_page(max_size)? {
+            regions.extend(page_regions);
+        }
+
+        Ok(regions)
+    }
+
+    /// Packs as many of the added images as fit into a single texture and uploads it,
+    /// `None` once every image is in a texture
+    pub fn build_page(&mut self, max_size: u32) -> Result<Option<AtlasPage>, Error> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.sort_by_key(|p| std::cmp::Reverse(p.image.height()));
+
+        let (atlas, placed, leftover) = pack_page(pending, max_size)?;
+        let texture = Texture::from_rgba(atlas.width(), atlas.height(), atlas.as_raw());
+        let mut regions = HashMap::new();
+
+        for (image, x, y) in placed {
+            for slice in image.slices {
+                let uv = glam::vec4(
+                    (x + slice.x) as f32 / atlas.width() as f32,
+                    (y + slice.y) as f32 / atlas.height() as f32,
+                    (x + slice.x + slice.width) as f32 / atlas.width() as f32,
+                    (y + slice.y + slice.height) as f32 / atlas.height() as f32,
+                );
+
+                regions.insert(slice.name, TextureRegion::new(texture.clone(), uv));
+            }
+        }
+
+        self.pending = leftover;
+
+        Ok(Some((texture, regions)))
+    }
+}
+
+/// Fills a single atlas page, what doesn't fit is returned to be packed into the next page
+#[allow(clippy::type_complexity)]
+fn pack_page(
+    pending: Vec<PendingImage>,
+    max_size: u32,
+) -> Result<(RgbaImage, Vec<(PendingImage, u32, u32)>, Vec<PendingImage>), Error> {
+    let mut placed = vec![];
+    let mut leftover = vec![];
+
+    let (mut cursor_x, mut cursor_y, mut shelf_height) = (0, 0, 0);
+    let (mut used_width, mut used_height) = (0, 0);
+
+    for image in pending {
+        let width = image.image.width() + PADDING * 2;
+        let height = image.image.height() + PADDING * 2;
+
+        if width > max_size || height > max_size {
+            return Err(Error::Other(format!(
+                "Image of {}x{} doesn't fit into an atlas of {}x{}",
+                image.image.width(),
+                image.image.height(),
+                max_size,
+                max_size
+            )));
+        }
+
+        if cursor_x + width > max_size {
+            cursor_x = 0;
+            cursor_y += shelf_height;
+            shelf_height = 0;
+        }
+
+        if cursor_y + height > max_size {
+            leftover.push(image);
+            continue;
+        }
+
+        placed.push((image, cursor_x + PADDING, cursor_y + PADDING));
+
+        cursor_x += width;
+        shelf_height = shelf_height.max(height);
+        used_width = used_width.max(cursor_x);
+        used_height = used_height.max(cursor_y + shelf_height);
+    }
+
+    let mut atlas = RgbaImage::new(
+        used_width.next_power_of_two(),
+        used_height.next_power_of_two(),
+    );
+
+    for (image, x, y) in placed.iter() {
+        blit_extruded(&mut atlas, &image.image, *x, *y);
+    }
+
+    Ok((atlas, placed, leftover))
+}
+
+/// Copies `image` into `atlas` at `x`, `y` and repeats its border pixels into the padding
+fn blit_extruded(atlas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32) {
+    let (width, height) = image.dimensions();
+
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    for dy in -(PADDING as i64)..(height + PADDING) as i64 {
+        for dx in -(PADDING as i64)..(width + PADDING) as i64 {
+            let source_x = dx.clamp(0, width as i64 - 1) as u32;
+            let source_y = dy.clamp(0, height as i64 - 1) as u32;
+
+            atlas.put_pixel(
+                (x as i64 + dx) as u32,
+                (y as i64 + dy) as u32,
+                *image.get_pixel(source_x, source_y),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn pending(width: u32, height: u32) -> PendingImage {
+        PendingImage {
+            image: RgbaImage::new(width, height),
+            slices: vec![],
+        }
+    }
+
+    fn positions(placed: &[(PendingImage, u32, u32)]) -> Vec<(u32, u32)> {
+        placed.iter().map(|(_, x, y)| (*x, *y)).collect()
+    }
+
+    #[test]
+    fn grid_slices_are_named_in_reading_order() {
+        let slices = grid_slices("walk", 100, 64, 32, 32).unwrap();
+
+        // The leftover column of 4 pixels isn't a cell
+        assert_eq!(slices.len(), 6);
+        assert_eq!(
+            slices[4],
+            Slice {
+                name: String::from("walk/4"),
+                x: 32,
+                y: 32,
+                width: 32,
+                height: 32,
+            }
+        );
+    }
+
+    #[test]
+    fn grid_slices_need_a_cell_size() {
+        assert!(grid_slices("walk", 100, 64, 0, 32).is_err());
+        assert!(grid_slices("walk", 100, 64, 32, 0).is_err());
+    }
+
+    #[test]
+    fn slices_have_to_be_inside_of_their_image() {
+        let slice = |x, width| Slice {
+            name: String::from("bad"),
+            x,
+            y: 0,
+            width,
+            height: 1,
+        };
+        let mut builder = AtlasBuilder::new();
+
+        for slice in [slice(10, 7), slice(u32::MAX, 2)] {
+            assert!(matches!(
+                builder.add_sliced("sheet.json", RgbaImage::new(16, 16), vec![slice]),
+                Err(Error::InvalidAsset { .. })
+            ));
+        }
+
+        assert!(builder.pending.is_empty());
+        assert!(builder
+            .add_sliced("sheet.json", RgbaImage::new(16, 16), vec![slice(10, 6)])
+            .is_ok());
+    }
+
+    #[test]
+    fn images_are_packed_into_shelves() {
+        let (atlas, placed, leftover) =
+            pack_page(vec![pending(30, 20), pending(30, 10), pending(30, 10)], 64).unwrap();
+
+        // Every image has a border of PADDING, the third one starts a new shelf below the first
+        assert_eq!(positions(&placed), [(1, 1), (33, 1), (1, 23)]);
+        assert!(leftover.is_empty());
+        assert_eq!(atlas.dimensions(), (64, 64));
+    }
+
+    #[test]
+    fn atlas_is_only_as_big_as_needed() {
+        let (atlas, _, _) = pack_page(vec![pending(10, 5)], 1024).unwrap();
+
+        assert_eq!(atlas.dimensions(), (16, 8));
+    }
+
+    #[test]
+    fn images_which_do_not_fit_go_to_the_next_page() {
+        let (_, placed, leftover) =
+            pack_page(vec![pending(20, 20), pending(20, 20), pending(20, 20)], 32).unwrap();
+
+        assert_eq!(positions(&placed), [(1, 1)]);
+        assert_eq!(leftover.len(), 2);
+    }
+
+    #[test]
+    fn images_bigger_than_a_page_are_rejected() {
+        assert!(pack_page(vec![pending(31, 4)], 32).is_err());
+    }
+
+    #[test]
+    fn edges_are_extruded_into_the_padding() {
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+        let mut image = RgbaImage::from_pixel(2, 2, red);
+        image.put_pixel(1, 1, blue);
+
+        let mut atlas = RgbaImage::new(4, 4);
+        blit_extruded(&mut atlas, &image, 1, 1);
+
+        // Corners of the padding repeat the corners of the image
+        assert_eq!(*atlas.get_pixel(0, 0), red);
+        assert_eq!(*atlas.get_pixel(3, 3), blue);
+        assert_eq!(*atlas.get_pixel(3, 0), red);
+        // Edges repeat the nearest pixel of the image
+        assert_eq!(*atlas.get_pixel(2, 3), blue);
+        assert_eq!(*atlas.get_pixel(0, 2), red);
+        assert_eq!(*atlas.get_pixel(2, 2), blue);
+    }
+}
@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+
+use ab_glyph::{Font as _, FontVec, InvalidFont, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+
+use super::atlas::{AtlasBuilder, Slice, TextureRegion};
+use crate::engine::{
+    error::Error,
+    vfs::{self, Vfs},
+};
+
+/// Characters rasterized from TrueType fonts, printable ASCII and Latin-1
+const CHARSET: [(char, char); 2] = [(' ', '~'), ('\u{a0}', '\u{ff}')];
+
+/// Drawn in place of characters the font doesn't have
+const FALLBACK: char = '?';
+
+/// How lines are placed relative to the x coordinate text is drawn at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    /// Lines start at x
+    Left,
+    /// Lines are centered on x
+    Center,
+    /// Lines end at x
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    /// Multiplies the size the font was loaded at
+    pub scale: f32,
+    pub color: glam::Vec4,
+    pub align: Align,
+    /// Lines longer than this are wrapped at spaces, words longer than a whole line overflow
+    pub max_width: Option<f32>,
+    /// Multiplies the line height of the font
+    pub line_spacing: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            color: glam::Vec4::ONE,
+            align: Align::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Glyph {
+    /// From the pen position on the baseline to the top left corner of the glyph
+    offset: glam::Vec2,
+    size: glam::Vec2,
+    advance: f32,
+}
+
+/// A glyph placed by `Font::layout`, ready to be drawn
+#[derive(Debug, Clone)]
+pub struct PositionedGlyph {
+    pub region: TextureRegion,
+    pub position: glam::Vec2,
+    pub size: glam::Vec2,
+}
+
+/// Glyphs of a font packed into atlas pages of their own, either rasterized from a TrueType
+/// font at a fixed pixel size or loaded from an AngelCode BMFont bitmap font
+pub struct Font {
+    glyphs: HashMap<char, Glyph>,
+    /// Whitespace has nothing to draw and no region
+    regions: HashMap<char, TextureRegion>,
+    kerning: HashMap<(char, char), f32>,
+    line_height: f32,
+    /// Distance from the top of a line to its baseline
+    ascent: f32,
+}
+
+/// A font with its glyph images ready but not uploaded yet, it can be made on any thread
+/// and is turned into a `Font` on the one owning the GL context
+pub struct FontData {
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), f32>,
+    line_height: f32,
+    ascent: f32,
+    builder: AtlasBuilder,
+}
+
+impl Font {
+    /// Height of a line in pixels at a scale of 1
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Extra space between two characters, usually negative to tuck them closer together
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kerning.get(&(first, second)).cloned().unwrap_or(0.0)
+    }
+
+    /// Places the glyphs of `text`, `position` is the top of the first line and the x
+    /// coordinate lines are aligned to
+    pub fn layout(
+        &self,
+        text: &str,
+        position: glam::Vec2,
+        style: &TextStyle,
+    ) -> Vec<PositionedGlyph> {
+        self.place(text, position, style)
+            .into_iter()
+            .filter_map(|(c, position, size)| {
+                Some(PositionedGlyph {
+                    region: self.glyph(c).1?.clone(),
+                    position,
+                    size,
+                })
+            })
+            .collect()
+    }
+
+    /// Top left corner and size of every character of `text` as laid out, including those
+    /// with nothing to draw
+    fn place(
+        &self,
+        text: &str,
+        position: glam::Vec2,
+        style: &TextStyle,
+    ) -> Vec<(char, glam::Vec2, glam::Vec2)> {
+        let mut placed = vec![];
+        let mut baseline = position.y + self.ascent * style.scale;
+
+        for line in self.wrap(text, style) {
+            let mut pen = match style.align {
+                Align::Left => position.x,
+                Align::Center => position.x - self.line_width(line, style.scale) / 2.0,
+                Align::Right => position.x - self.line_width(line, style.scale),
+            };
+            let mut previous = None;
+
+            for c in line.chars() {
+                let glyph = self.glyph(c).0;
+
+                if let Some(previous) = previous {
+                    pen += self.kerning(previous, c) * style.scale;
+                }
+
+                placed.push((
+                    c,
+                    // Snapped to whole pixels so unscaled text stays crisp
+                    (glam::vec2(pen, baseline) + glyph.offset * style.scale).round(),
+                    glyph.size * style.scale,
+                ));
+
+                pen += glyph.advance * style.scale;
+                previous = Some(c);
+            }
+
+            baseline += self.scaled_line_height(style);
+        }
+
+        placed
+    }
+
+    /// Metrics and image of a character, or of the fallback if the font doesn't have it
+    fn glyph(&self, c: char) -> (Glyph, Option<&TextureRegion>) {
+        let c = if self.glyphs.contains_key(&c) {
+            c
+        } else {
+            FALLBACK
+        };
+
+        (
+            self.glyphs.get(&c).copied().unwrap_or_default(),
+            self.regions.get(&c),
+        )
+    }
+
+    fn scaled_line_height(&self, style: &TextStyle) -> f32 {
+        self.line_height * style.scale * style.line_spacing
+    }
+
+    fn line_width(&self, line: &str, scale: f32) -> f32 {
+        let mut width = 0.0;
+        let mut previous = None;
+
+        for c in line.chars() {
+            if let Some(previous) = previous {
+                width += self.kerning(previous, c);
+            }
+
+            width += self.glyph(c).0.advance;
+            previous = Some(c);
+        }
+
+        width * scale
+    }
+
+    /// Splits text into lines at line breaks and, with a maximum width, greedily at spaces
+    fn wrap<'a>(&self, text: &'a str, style: &TextStyle) -> Vec<&'a str> {
+        let mut lines = vec![];
+
+        for paragraph in text.lines() {
+            let max_width = match style.max_width {
+                Some(max_width) => max_width,
+                None => {
+                    lines.push(paragraph);
+                    continue;
+                }
+            };
+
+            let (mut line_start, mut line_end) = (0, 0);
+            let mut word_start = 0;
+
+            for word in paragraph.split(' ') {
+                let word_end = word_start + word.len();
+
+                if !word.is_empty() {
+                    let candidate = &paragraph[line_start..word_end];
+
+                    if line_end > line_start && self.line_width(candidate, style.scale) > max_width
+                    {
+                        lines.push(&paragraph[line_start..line_end]);
+                        line_start = word_start;
+                    }
+
+                    line_end = word_end;
+                }
+
+                word_start = word_end + 1;
+            }
+
+            lines.push(&paragraph[line_start..line_end]);
+        }
+
+        lines
+    }
+}
+
+impl FontData {
+    /// Rasterizes the glyphs of a TrueType or OpenType font file at `pixel_size`
+    pub fn from_ttf(data: Vec<u8>, pixel_size: f32) -> Result<Self, InvalidFont> {
+        let font = FontVec::try_from_vec(data)?;
+
+        let scaled = font.as_scaled(PxScale::from(pixel_size));
+
+        let characters: Vec<char> = CHARSET
+            .iter()
+            .flat_map(|&(first, last)| first..=last)
+            .filter(|&c| font.glyph_id(c).0 != 0)
+            .collect();
+
+        let mut builder = AtlasBuilder::new();
+        let mut glyphs = HashMap::new();
+
+        for &c in characters.iter() {
+            let id = font.glyph_id(c);
+            let mut glyph = Glyph {
+                offset: glam::Vec2::ZERO,
+                size: glam::Vec2::ZERO,
+                advance: scaled.h_advance(id),
+            };
+
+            let outline = font.outline_glyph(id.with_scale(pixel_size));
+
+            if let Some(outline) = outline {
+                let bounds = outline.px_bounds();
+                let mut image = RgbaImage::from_pixel(
+                    bounds.width() as u32,
+                    bounds.height() as u32,
+                    Rgba([255, 255, 255, 0]),
+                );
+
+                outline.draw(|x, y, coverage| {
+                    let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                    image.put_pixel(x, y, Rgba([255, 255, 255, alpha]));
+                });
+
+                glyph.offset = glam::vec2(bounds.min.x, bounds.min.y);
+                glyph.size = glam::vec2(image.width() as f32, image.height() as f32);
+
+                if image.width() > 0 && image.height() > 0 {
+                    builder.add(&region_name(c), image);
+                }
+            }
+
+            glyphs.insert(c, glyph);
+        }
+
+        let mut kerning = HashMap::new();
+
+        for &first in characters.iter() {
+            for &second in characters.iter() {
+                let amount = scaled.kern(font.glyph_id(first), font.glyph_id(second));
+
+                if amount != 0.0 {
+                    kerning.insert((first, second), amount);
+                }
+            }
+        }
+
+        Ok(Self {
+            glyphs,
+            kerning,
+            line_height: scaled.height() + scaled.line_gap(),
+            ascent: scaled.ascent(),
+            builder,
+        })
+    }
+
+    /// Loads a bitmap font in the text format of AngelCode BMFont, page images are looked up
+    /// next to the descriptor
+    pub fn from_bmfont(vfs: &Vfs, path: &str) -> Result<Self, Error> {
+        let descriptor = vfs.read_to_string(path)?;
+
+        let mut line_height = 0.0;
+        let mut ascent = 0.0;
+        let mut pages: HashMap<u32, String> = HashMap::new();
+        let mut slices: HashMap<u32, Vec<Slice>> = HashMap::new();
+        let mut glyphs = HashMap::new();
+        let mut kerning = HashMap::new();
+
+        for (index, line) in descriptor.lines().enumerate() {
+            let (tag, attributes) = parse_bmfont_line(line);
+            let error = |key: &str| {
+                Error::invalid_asset(
+                    path,
+                    format!("line {}: missing or invalid '{}'", index + 1, key),
+                )
+            };
+            let number = |key: &str| -> Result<i32, Error> {
+                attributes
+                    .get(key)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| error(key))
+            };
+            // Ids and positions in the page images can't be negative
+            let unsigned = |key: &str| -> Result<u32, Error> {
+                u32::try_from(number(key)?).map_err(|_| error(key))
+            };
+
+            match tag {
+                "common" => {
+                    line_height = number("lineHeight")? as f32;
+                    ascent = number("base")? as f32;
+                }
+                "page" => {
+                    let file = attributes.get("file").ok_or_else(|| error("file"))?;
+                    pages.insert(unsigned("id")?, file.clone());
+                }
+                "char" => {
+                    let c = char::from_u32(unsigned("id")?).ok_or_else(|| error("id"))?;
+                    let (width, height) = (unsigned("width")?, unsigned("height")?);
+
+                    if width > 0 && height > 0 {
+                        slices.entry(unsigned("page")?).or_default().push(Slice {
+                            name: region_name(c),
+                            x: unsigned("x")?,
+                            y: unsigned("y")?,
+                            width,
+                            height,
+                        });
+                    }
+
+                    glyphs.insert(
+                        c,
+                        Glyph {
+                            offset: glam::vec2(
+                                number("xoffset")? as f32,
+                                number("yoffset")? as f32,
+                            ),
+                            size: glam::vec2(width as f32, height as f32),
+                            advance: number("xadvance")? as f32,
+                        },
+                    );
+                }
+                "kerning" => {
+                    let first = char::from_u32(unsigned("first")?);
+                    let second = char::from_u32(unsigned("second")?);
+
+                    if let (Some(first), Some(second)) = (first, second) {
+                        kerning.insert((first, second), number("amount")? as f32);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Offsets are relative to the top of the line, ours to the baseline
+        for glyph in glyphs.values_mut() {
+            glyph.offset.y -= ascent;
+        }
+
+        let mut builder = AtlasBuilder::new();
+
+        for (page, page_slices) in slices {
+            let file = pages.get(&page).ok_or_else(|| {
+                Error::invalid_asset(path, format!("glyphs on undeclared page {}", page))
+            })?;
+            let file = vfs::join(path, file);
+            let image = image::load_from_memory(&vfs.read(&file)?)
+                .map_err(|source| Error::Image {
+                    path: Some(file),
+                    source,
+                })?
+                .to_rgba8();
+
+            builder.add_sliced(path, image, page_slices)?;
+        }
+
+        Ok(Self {
+            glyphs,
+            kerning,
+            line_height,
+            ascent,
+            builder,
+        })
+    }
+
+    /// Packs the glyphs into atlas pages
+    pub fn upload(mut self, max_atlas_size: u32) -> Result<Font, Error> {
+        let regions = self.builder.build(max_atlas_size)?;
+        let regions = self
+            .glyphs
+            .keys()
+            .filter_map(|&c| Some((c, regions.get(&region_name(c))?.clone())))
+            .collect();
+
+        Ok(Font {
+            glyphs: self.glyphs,
+            regions,
+            kerning: self.kerning,
+            line_height: self.line_height,
+            ascent: self.ascent,
+        })
+    }
+}
+
+/// Glyphs are packed under their code point
+fn region_name(c: char) -> String {
+    (c as u32).to_string()
+}
+
+/// Splits a line like `char id=65 x=2 y=0 file="font 0.png"` into its tag and attributes
+fn parse_bmfont_line(line: &str) -> (&str, HashMap<&str, String>) {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+    let mut attributes = HashMap::new();
+
+    while let Some((key, after_key)) = rest.trim_start().split_once('=') {
+        let (value, after_value) = match after_key.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after_key.split_once(' ').unwrap_or((after_key, "")),
+        };
+
+        attributes.insert(key, value.to_string());
+        rest = after_value;
+    }
+
+    (tag, attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A font without glyph images, `a` and `b` kern by -2
+    fn font() -> Font {
+        let glyph = |advance, size| Glyph {
+            offset: glam::vec2(1.0, -10.0),
+            size,
+            advance,
+        };
+
+        Font {
+            glyphs: HashMap::from([
+                ('a', glyph(10.0, glam::vec2(8.0, 12.0))),
+                ('b', glyph(12.0, glam::vec2(10.0, 12.0))),
+                (' ', glyph(5.0, glam::Vec2::ZERO)),
+                (FALLBACK, glyph(7.0, glam::vec2(6.0, 12.0))),
+            ]),
+            regions: HashMap::new(),
+            kerning: HashMap::from([(('a', 'b'), -2.0)]),
+            line_height: 20.0,
+            ascent: 15.0,
+        }
+    }
+
+    fn wrapped(max_width: f32, text: &str) -> Vec<&str> {
+        font().wrap(
+            text,
+            &TextStyle {
+                max_width: Some(max_width),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn positions(text: &str, style: &TextStyle) -> Vec<glam::Vec2> {
+        font()
+            .place(text, glam::vec2(100.0, 0.0), style)
+            .into_iter()
+            .map(|(_, position, _)| position)
+            .collect()
+    }
+
+    #[test]
+    fn line_width_adds_advances_and_kerning() {
+        let font = font();
+
+        assert_eq!(font.line_width("ab", 1.0), 20.0);
+        assert_eq!(font.line_width("ba", 1.0), 22.0);
+        assert_eq!(font.line_width("ab", 2.0), 40.0);
+        // Missing characters take up the space of the fallback
+        assert_eq!(font.line_width("z", 1.0), 7.0);
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        assert_eq!(wrapped(50.0, "aa aa aa"), ["aa aa", "aa"]);
+        assert_eq!(wrapped(45.0, "aa aa"), ["aa aa"]);
+    }
+
+    #[test]
+    fn words_longer_than_a_line_overflow() {
+        assert_eq!(wrapped(30.0, "aaaaaa b"), ["aaaaaa", "b"]);
+    }
+
+    #[test]
+    fn line_breaks_are_kept() {
+        let font = font();
+
+        assert_eq!(font.wrap("a\nb", &TextStyle::default()), ["a", "b"]);
+        assert_eq!(wrapped(100.0, "a\n\nb"), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn lines_are_aligned_to_the_position() {
+        let style = |align| TextStyle {
+            align,
+            ..Default::default()
+        };
+
+        // "ab" is 20 wide, glyphs are drawn 1 right of the pen and 10 above the baseline
+        assert_eq!(
+            positions("ab", &style(Align::Left))[0],
+            glam::vec2(101.0, 5.0)
+        );
+        assert_eq!(
+            positions("ab", &style(Align::Center))[0],
+            glam::vec2(91.0, 5.0)
+        );
+        assert_eq!(
+            positions("ab", &style(Align::Right))[0],
+            glam::vec2(81.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn layout_applies_kerning() {
+        let placed = positions("aab", &TextStyle::default());
+
+        assert_eq!(placed[1].x - placed[0].x, 10.0);
+        assert_eq!(placed[2].x - placed[1].x, 8.0);
+    }
+
+    #[test]
+    fn following_lines_move_down_by_the_line_height() {
+        let placed = positions(
+            "a\na",
+            &TextStyle {
+                scale: 2.0,
+                line_spacing: 1.5,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(placed[0], glam::vec2(102.0, 10.0));
+        assert_eq!(placed[1], glam::vec2(102.0, 70.0));
+    }
+
+    #[test]
+    fn parses_bmfont_attributes() {
+        let (tag, attributes) =
+            parse_bmfont_line(r#"  page id=0 file="font 0.png"  x=-3 empty="" "#);
+
+        assert_eq!(tag, "page");
+        assert_eq!(attributes.len(), 4);
+        assert_eq!(attributes["id"], "0");
+        assert_eq!(attributes["file"], "font 0.png");
+        assert_eq!(attributes["x"], "-3");
+        assert_eq!(attributes["empty"], "");
+    }
+
+    #[test]
+    fn parses_a_line_without_attributes() {
+        let (tag, attributes) = parse_bmfont_line("info");
+
+        assert_eq!(tag, "info");
+        assert!(attributes.is_empty());
+    }
+
+    #[test]
+    fn negative_bmfont_positions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("font.fnt"),
+            "common lineHeight=20 base=15\n\
+             page id=0 file=\"font.png\"\n\
+             char id=65 x=-1 y=0 width=8 height=8 xoffset=0 yoffset=0 xadvance=9 page=0\n",
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.mount_dir("", dir.path());
+
+        match FontData::from_bmfont(&vfs, "font.fnt") {
+            Err(Error::InvalidAsset { path, source }) => {
+                assert_eq!(path, "font.fnt");
+                assert_eq!(source.to_string(), "line 3: missing or invalid 'x'");
+            }
+            Err(e) => panic!("expected an invalid asset, got {}", e),
+            Ok(_) => panic!("expected an invalid asset"),
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use image::{DynamicImage, RgbaImage};
+
+use super::{
+    atlas::Slice,
+    font::FontData,
+    sound::{Music, Sound},
+    texture::TextureOptions,
+};
+use crate::engine::{error::Error, vfs::Vfs};
+
+/// Decoding is mostly waiting on memory, more threads than this rarely help
+const MAX_WORKERS: usize = 4;
+
+/// An asset decoded on a loader thread, what's left to do needs the GL context or the
+/// resource manager and happens on the main thread
+pub enum Decoded {
+    Texture {
+        name: String,
+        image: DynamicImage,
+        options: TextureOptions,
+    },
+    AtlasImage {
+        name: String,
+        image: RgbaImage,
+    },
+    SpriteSheet {
+        name: String,
+        /// File the slices were read from
+        path: String,
+        image: RgbaImage,
+        slices: Vec<Slice>,
+    },
+    Font {
+        name: String,
+        font: FontData,
+    },
+    Sound {
+        name: String,
+        sound: Sound,
+    },
+    Music {
+        name: String,
+        music: Music,
+    },
+    /// A file parsed by the game into a type of its own
+    Data {
+        path: String,
+        data: Box<dyn Any + Send>,
+    },
+}
+
+type Job = Box<dyn FnOnce(&Vfs) -> Result<Decoded, Error> + Send>;
+
+/// How far along background loading is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    /// From 0.0 to 1.0, nothing to load counts as done
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.loaded >= self.total
+    }
+}
+
+/// Runs decoding jobs on a pool of threads, which exit once the loader is dropped
+pub struct AssetLoader {
+    jobs: Sender<Job>,
+    results: Receiver<Result<Decoded, Error>>,
+    queued: usize,
+    received: usize,
+}
+
+impl AssetLoader {
+    pub fn new(vfs: &Vfs) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(MAX_WORKERS);
+
+        for _ in 0..workers {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let vfs = vfs.clone();
+
+            thread::Builder::new()
+                .name(String::from("asset loader"))
+                .spawn(move || loop {
+                    // The lock is only held while waiting for a job, not while running it
+                    let job = match job_receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+
+                    // A panicking job fails its asset instead of taking the worker down with it
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| job(&vfs)))
+                        .unwrap_or_else(|payload| Err(panicked(payload)));
+
+                    if result_sender.send(result).is_err() {
+                        break;
+                    }
+                })
+                .expect("Couldn't start an asset loader thread");
+        }
+
+        Self {
+            jobs,
+            results,
+            queued: 0,
+            received: 0,
+        }
+    }
+
+    pub fn queue(&mut self, job: impl FnOnce(&Vfs) -> Result<Decoded, Error> + Send + 'static) {
+        self.queued += 1;
+        // Workers only stop once the loader is dropped, so the job always reaches one
+        let _ = self.jobs.send(Box::new(job));
+    }
+
+    /// The next decoded asset, `None` once everything queued has been received or when
+    /// nothing is ready yet
+    pub fn next(&mut self) -> Option<Result<Decoded, Error>> {
+        if self.received == self.queued {
+            return None;
+        }
+
+        match self.results.try_recv() {
+            Ok(decoded) => {
+                self.received += 1;
+                Some(decoded)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                // Nothing more is coming, count the rest as received so this is reported once
+                self.received = self.queued;
+                Some(Err(Error::Other(String::from(
+                    "The asset loader threads stopped",
+                ))))
+            }
+        }
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        LoadProgress {
+            loaded: self.received,
+            total: self.queued,
+        }
+    }
+}
+
+/// Turns what a job panicked with into an error, panics carry a `&str` or a `String`
+fn panicked(payload: Box<dyn Any + Send>) -> Error {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+
+    Error::Other(format!("An asset loader job panicked: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Everything `loader` decodes, waiting until it's all there
+    fn receive_all(loader: &mut AssetLoader) -> Vec<Result<Decoded, Error>> {
+        let start = Instant::now();
+        let mut results = vec![];
+
+        while !loader.progress().is_done() {
+            assert!(start.elapsed() < Duration::from_secs(10), "loading hangs");
+
+            match loader.next() {
+                Some(result) => results.push(result),
+                None => thread::yield_now(),
+            }
+        }
+
+        results
+    }
+
+    fn data(path: &str) -> Result<Decoded, Error> {
+        Ok(Decoded::Data {
+            path: path.to_string(),
+            data: Box::new(()),
+        })
+    }
+
+    #[test]
+    fn a_panicking_job_fails_only_its_asset() {
+        let mut loader = AssetLoader::new(&Vfs::new());
+
+        // More panics than workers, so every worker has to survive one
+        for _ in 0..MAX_WORKERS + 1 {
+            loader.queue(|_| panic!("bad asset"));
+        }
+        for _ in 0..MAX_WORKERS + 1 {
+            loader.queue(|_| data("good"));
+        }
+
+        let results = receive_all(&mut loader);
+        let errors: Vec<String> = results
+            .iter()
+            .filter_map(|result| result.as_ref().err().map(Error::to_string))
+            .collect();
+
+        assert_eq!(results.len(), 2 * (MAX_WORKERS + 1));
+        assert_eq!(errors.len(), MAX_WORKERS + 1);
+        assert!(errors
+            .iter()
+            .all(|e| e == "An asset loader job panicked: bad asset"));
+    }
+
+    #[test]
+    fn progress_counts_received_assets() {
+        let mut loader = AssetLoader::new(&Vfs::new());
+        assert!(loader.next().is_none());
+        assert_eq!(loader.progress().fraction(), 1.0);
+
+        loader.queue(|_| data("a"));
+        loader.queue(|_| Err(Error::Other(String::from("missing"))));
+        assert_eq!(
+            loader.progress(),
+            LoadProgress {
+                loaded: 0,
+                total: 2
+            }
+        );
+
+        assert_eq!(receive_all(&mut loader).len(), 2);
+        assert_eq!(
+            loader.progress(),
+            LoadProgress {
+                loaded: 2,
+                total: 2
+            }
+        );
+        assert!(loader.next().is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use super::texture::TextureOptions;
+use crate::engine::{
+    error::Error,
+    vfs::{self, Vfs},
+};
+
+/// Every asset of a game, described in a TOML file so assets can be added without recompiling.
+/// Entries are keyed by the name they're loaded under, paths are relative to the manifest file.
+///
+/// ```toml
+/// [shaders.sprite]
+/// vertex = "shaders/sprite.vert"
+/// fragment = "shaders/sprite.frag"
+///
+/// [textures.background]
+/// path = "textures/background.jpeg"
+/// alpha = false
+/// mipmaps = true
+///
+/// [atlas.paddle]
+/// path = "textures/paddle.png"
+///
+/// [sprite_sheets.explosion]
+/// path = "textures/explosion.png"
+/// cell_width = 64
+/// cell_height = 64
+///
+/// [fonts.hud]
+/// path = "fonts/DejaVuSans.ttf"
+/// size = 24
+///
+/// [sounds.hit]
+/// path = "audio/hit.wav"
+///
+/// [music.theme]
+/// path = "audio/theme.ogg"
+///
+/// [level_packs.main]
+/// levels = ["levels/1.level", "levels/2.level"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssetManifest {
+    pub shaders: BTreeMap<String, ShaderEntry>,
+    /// Standalone textures, for images too big or too special to share an atlas
+    pub textures: BTreeMap<String, TextureEntry>,
+    /// Images packed into the shared atlases, available as regions
+    pub atlas: BTreeMap<String, AtlasEntry>,
+    pub sprite_sheets: BTreeMap<String, SpriteSheetEntry>,
+    pub fonts: BTreeMap<String, FontEntry>,
+    pub sounds: BTreeMap<String, AudioEntry>,
+    pub music: BTreeMap<String, AudioEntry>,
+    pub level_packs: BTreeMap<String, LevelPackEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShaderEntry {
+    pub vertex: String,
+    pub fragment: String,
+    pub geometry: Option<String>,
+}
+
+/// Unknown fields aren't caught here, serde can't tell them apart from the flattened options
+#[derive(Debug, Deserialize)]
+pub struct TextureEntry {
+    pub path: String,
+    /// Any field of `TextureOptions`, e.g. `alpha = false` or `min_filter = "nearest"`
+    #[serde(flatten)]
+    pub options: TextureOptions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AtlasEntry {
+    pub path: String,
+}
+
+/// Either a grid of equally sized cells, which become regions called `<name>/<index>`,
+/// or regions described by a JSON file
+#[derive(Debug, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum SpriteSheetEntry {
+    Grid {
+        path: String,
+        cell_width: u32,
+        cell_height: u32,
+    },
+    Described {
+        path: String,
+        /// JSON file listing the regions, see `ResourceManager::add_sprite_sheet_json`
+        regions: String,
+    },
+}
+
+/// A TrueType or OpenType font rasterized at `size` pixels, without a size `path` is the
+/// descriptor of an AngelCode BMFont bitmap font
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FontEntry {
+    pub path: String,
+    pub size: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AudioEntry {
+    pub path: String,
+}
+
+/// Level files played in order
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelPackEntry {
+    pub levels: Vec<String>,
+}
+
+impl AssetManifest {
+    /// Reads a manifest and makes its paths relative to the root of the file system
+    pub fn from_file(vfs: &Vfs, path: &str) -> Result<Self, Error> {
+        let source = vfs.read_to_string(path)?;
+        let mut manifest: Self =
+            toml::from_str(&source).map_err(|e| Error::invalid_asset(path, e))?;
+        manifest.resolve_paths(path);
+
+        Ok(manifest)
+    }
+
+    fn resolve_paths(&mut self, manifest_path: &str) {
+        let resolve = |path: &mut String| {
+            *path = vfs::join(manifest_path, path);
+        };
+
+        for shader in self.shaders.values_mut() {
+            resolve(&mut shader.vertex);
+            resolve(&mut shader.fragment);
+            shader.geometry.as_mut().map(resolve);
+        }
+
+        self.textures
+            .values_mut()
+            .for_each(|texture| resolve(&mut texture.path));
+        self.atlas
+            .values_mut()
+            .for_each(|image| resolve(&mut image.path));
+
+        for sheet in self.sprite_sheets.values_mut() {
+            match sheet {
+                SpriteSheetEntry::Grid { path, .. } => resolve(path),
+                SpriteSheetEntry::Described { path, regions } => {
+                    resolve(path);
+                    resolve(regions);
+                }
+            }
+        }
+
+        self.fonts
+            .values_mut()
+            .for_each(|font| resolve(&mut font.path));
+        self.sounds
+            .values_mut()
+            .chain(self.music.values_mut())
+            .for_each(|audio| resolve(&mut audio.path));
+        self.level_packs
+            .values_mut()
+            .flat_map(|pack| pack.levels.iter_mut())
+            .for_each(resolve);
+    }
+}
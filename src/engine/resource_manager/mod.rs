@@ -1,21 +1,286 @@
-use std::collections::HashMap;
+use std::{
+    any::Any,
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime},
+};
 
-use self::{shader::Shader, texture::Texture};
+use gl::types::*;
+use image::DynamicImage;
 
+use self::{
+    atlas::{AtlasBuilder, SheetDescription, TextureRegion},
+    font::{Font, FontData},
+    loader::{AssetLoader, Decoded, LoadProgress},
+    manifest::{AssetManifest, FontEntry, SpriteSheetEntry},
+    shader::Shader,
+    sound::{Music, Sound},
+    texture::Texture,
+};
+use super::{error::Error, vfs::Vfs};
+
+pub mod atlas;
+pub mod font;
+pub mod loader;
+pub mod manifest;
 pub mod shader;
+pub mod sound;
 pub mod texture;
+pub mod uniform;
+
+/// Atlases are never made bigger than this even if the GPU would support it
+const MAX_ATLAS_SIZE: u32 = 4096;
+
+/// How often shader sources are checked for changes when hot-reloading is enabled
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Where a shader was loaded from, kept around to be able to reload it
+struct ShaderSource {
+    vertex_shader_path: String,
+    fragment_shader_path: String,
+    geometry_shader_path: Option<String>,
+    modified: Option<SystemTime>,
+}
+
+impl ShaderSource {
+    fn paths(&self) -> impl Iterator<Item = &str> {
+        [
+            Some(self.vertex_shader_path.as_str()),
+            Some(self.fragment_shader_path.as_str()),
+            self.geometry_shader_path.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
 
+    /// Latest modification time of any of the source files
+    fn last_modified(&self, vfs: &Vfs) -> Option<SystemTime> {
+        self.paths().filter_map(|path| vfs.modified(path)).max()
+    }
+}
+
+/// Loads assets from a virtual file system and keeps them by name
 pub struct ResourceManager {
-    shaders: HashMap<&'static str, Shader>,
-    textures: HashMap<&'static str, Texture>,
+    vfs: Vfs,
+    shaders: HashMap<String, Shader>,
+    shader_sources: HashMap<String, ShaderSource>,
+    hot_reload: bool,
+    last_reload_check: Instant,
+    textures: HashMap<String, Texture>,
+    atlas_builder: AtlasBuilder,
+    atlases: Vec<Texture>,
+    regions: HashMap<String, TextureRegion>,
+    fonts: HashMap<String, Font>,
+    sounds: HashMap<String, Sound>,
+    music: HashMap<String, Music>,
+    level_packs: HashMap<String, Vec<String>>,
+    /// Files parsed by `load_data_in_background`, until they're taken
+    data: HashMap<String, Box<dyn Any + Send>>,
+    /// Only while assets are loading in the background
+    loader: Option<AssetLoader>,
 }
 
 impl ResourceManager {
-    pub fn new() -> Self {
+    pub fn new(vfs: Vfs) -> Self {
         Self {
+            vfs,
             shaders: HashMap::new(),
+            shader_sources: HashMap::new(),
+            hot_reload: false,
+            last_reload_check: Instant::now(),
             textures: HashMap::new(),
+            atlas_builder: AtlasBuilder::new(),
+            atlases: vec![],
+            regions: HashMap::new(),
+            fonts: HashMap::new(),
+            sounds: HashMap::new(),
+            music: HashMap::new(),
+            level_packs: HashMap::new(),
+            data: HashMap::new(),
+            loader: None,
+        }
+    }
+
+    /// Starts loading everything listed in an asset manifest on worker threads, shaders and
+    /// level packs are ready right away. Call `process_loaded` every frame until everything
+    /// else is.
+    pub fn load_manifest_in_background(&mut self, manifest_path: &str) -> Result<(), Error> {
+        let manifest = AssetManifest::from_file(&self.vfs, manifest_path)?;
+
+        for (name, shader) in &manifest.shaders {
+            self.load_shader(
+                &shader.vertex,
+                &shader.fragment,
+                shader.geometry.as_deref(),
+                name,
+            )
+            .map_err(in_entry("Shader", name))?;
+        }
+
+        for (name, pack) in manifest.level_packs {
+            self.level_packs.insert(name, pack.levels);
+        }
+
+        let loader = self.loader();
+
+        for (name, texture) in manifest.textures {
+            loader.queue(move |vfs| {
+                let image = decode_image(vfs, &texture.path).map_err(in_entry("Texture", &name))?;
+
+                Ok(Decoded::Texture {
+                    name,
+                    image,
+                    options: texture.options,
+                })
+            });
+        }
+
+        for (name, image) in manifest.atlas {
+            loader.queue(move |vfs| {
+                let image = decode_image(vfs, &image.path)
+                    .map_err(in_entry("Atlas image", &name))?
+                    .to_rgba8();
+
+                Ok(Decoded::AtlasImage { name, image })
+            });
+        }
+
+        for (name, sheet) in manifest.sprite_sheets {
+            loader.queue(move |vfs| {
+                let (path, image, slices) = match sheet {
+                    SpriteSheetEntry::Grid {
+                        path,
+                        cell_width,
+                        cell_height,
+                    } => decode_image(vfs, &path).and_then(|image| {
+                        let image = image.to_rgba8();
+                        let slices = atlas::grid_slices(
+                            &name,
+                            image.width(),
+                            image.height(),
+                            cell_width,
+                            cell_height,
+                        )?;
+
+                        Ok((path, image, slices))
+                    }),
+                    SpriteSheetEntry::Described { path, regions } => decode_image(vfs, &path)
+                        .and_then(|image| {
+                            let json = vfs.read_to_string(&regions)?;
+                            let description = SheetDescription::from_json(&json)
+                                .map_err(|e| Error::invalid_asset(&regions, e))?;
+
+                            Ok((regions, image.to_rgba8(), description.regions))
+                        }),
+                }
+                .map_err(in_entry("Sprite sheet", &name))?;
+
+                Ok(Decoded::SpriteSheet {
+                    name,
+                    path,
+                    image,
+                    slices,
+                })
+            });
         }
+
+        for (name, FontEntry { path, size }) in manifest.fonts {
+            loader.queue(move |vfs| {
+                let font = match size {
+                    Some(size) => vfs.read(&path).and_then(|data| {
+                        FontData::from_ttf(data, size).map_err(|e| Error::invalid_asset(&path, e))
+                    }),
+                    None => FontData::from_bmfont(vfs, &path),
+                }
+                .map_err(in_entry("Font", &name))?;
+
+                Ok(Decoded::Font { name, font })
+            });
+        }
+
+        for (name, sound) in manifest.sounds {
+            loader.queue(move |vfs| {
+                let sound = Sound::from_file(vfs, &sound.path).map_err(in_entry("Sound", &name))?;
+
+                Ok(Decoded::Sound { name, sound })
+            });
+        }
+
+        for (name, music) in manifest.music {
+            loader.queue(move |vfs| {
+                let music = Music::from_file(vfs, &music.path).map_err(in_entry("Music", &name))?;
+
+                Ok(Decoded::Music { name, music })
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reads and parses a file of the game's own on a worker thread, `parse` gets the path and
+    /// the contents of the file. The result is picked up with `take_data` once loading is done.
+    pub fn load_data_in_background<T: Send + 'static>(
+        &mut self,
+        path: &str,
+        parse: fn(&str, &[u8]) -> Result<T, Error>,
+    ) {
+        let path = path.to_string();
+
+        self.loader().queue(move |vfs| {
+            let data = parse(&path, &vfs.read(&path)?)?;
+
+            Ok(Decoded::Data {
+                path,
+                data: Box::new(data),
+            })
+        });
+    }
+
+    /// Finishes assets decoded in the background, uploading textures and fonts to the GPU until
+    /// `budget` runs out, meant to be called once per frame. Atlases are packed once everything
+    /// else is done, a page at a time within the same budget, and count as one more asset.
+    /// Loading stops at the first asset failing to load.
+    pub fn process_loaded(&mut self, budget: Duration) -> Result<LoadProgress, Error> {
+        let start = Instant::now();
+
+        let mut loader = match self.loader.take() {
+            Some(loader) => loader,
+            None => return Ok(LoadProgress::default()),
+        };
+
+        while start.elapsed() < budget {
+            match loader.next() {
+                Some(decoded) => self.finish_decoded(decoded?)?,
+                None => break,
+            }
+        }
+
+        let decoded = loader.progress();
+        let mut atlases_built = false;
+
+        if decoded.is_done() {
+            while start.elapsed() < budget {
+                if !self.build_atlas_page()? {
+                    atlases_built = true;
+                    break;
+                }
+            }
+        }
+
+        if !atlases_built {
+            self.loader = Some(loader);
+        }
+
+        Ok(LoadProgress {
+            loaded: decoded.loaded + atlases_built as usize,
+            total: decoded.total + 1,
+        })
+    }
+
+    /// Takes a file loaded with `load_data_in_background`, `None` if it isn't loaded or isn't a `T`
+    pub fn take_data<T: 'static>(&mut self, path: &str) -> Option<T> {
+        let data = self.data.remove(path)?;
+
+        data.downcast().ok().map(|data| *data)
     }
 
     pub fn load_shader(
@@ -23,39 +288,201 @@ impl ResourceManager {
         vertex_shader_path: &str,
         fragment_shader_path: &str,
         geometry_shader_path: Option<&str>,
-        name: &'static str,
-    ) -> Option<Shader> {
-        let shader = match Shader::from_source(
+        name: &str,
+    ) -> Result<(), Error> {
+        let shader = Shader::from_source(
+            &self.vfs,
             vertex_shader_path,
             fragment_shader_path,
             geometry_shader_path,
-        ) {
-            Ok(s) => s,
-            Err(_) => return None,
+        )?;
+
+        let source = ShaderSource {
+            vertex_shader_path: vertex_shader_path.to_string(),
+            fragment_shader_path: fragment_shader_path.to_string(),
+            geometry_shader_path: geometry_shader_path.map(String::from),
+            modified: None,
         };
 
-        self.shaders.insert(name, shader)
+        self.shader_sources.insert(
+            name.to_string(),
+            ShaderSource {
+                modified: source.last_modified(&self.vfs),
+                ..source
+            },
+        );
+
+        self.shaders.insert(name.to_string(), shader);
+
+        Ok(())
+    }
+
+    /// Development mode, when enabled `reload_changed_shaders` recompiles shaders whose sources changed on disk
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        self.hot_reload = enabled;
+    }
+
+    /// Recompiles every shader whose source files changed since they were last compiled, meant to be
+    /// called once per frame. A shader which compiles and links replaces the old program in place so
+    /// everyone holding it picks it up, otherwise the error is logged and the old program is kept.
+    pub fn reload_changed_shaders(&mut self) {
+        if !self.hot_reload || self.last_reload_check.elapsed() < HOT_RELOAD_INTERVAL {
+            return;
+        }
+
+        self.last_reload_check = Instant::now();
+
+        for (name, source) in self.shader_sources.iter_mut() {
+            let modified = source.last_modified(&self.vfs);
+
+            if modified <= source.modified {
+                continue;
+            }
+
+            // Remember the change even if compiling fails, so it's retried on the next save only
+            source.modified = modified;
+
+            let result = Shader::from_source(
+                &self.vfs,
+                &source.vertex_shader_path,
+                &source.fragment_shader_path,
+                source.geometry_shader_path.as_deref(),
+            );
+
+            match (result, self.shaders.get(name)) {
+                (Ok(reloaded), Some(shader)) => {
+                    shader.replace_with(reloaded);
+                    eprintln!("Reloaded shader '{}'", name);
+                }
+                (Ok(_), None) => (),
+                (Err(e), _) => eprintln!(
+                    "Couldn't reload shader '{}', keeping the old one: {}",
+                    name, e
+                ),
+            }
+        }
     }
 
     pub fn get_shader(&self, name: &str) -> Option<Shader> {
         self.shaders.get(name).cloned()
     }
 
-    pub fn load_texture(
-        &mut self,
-        image_path: &str,
-        alpha: bool,
-        name: &'static str,
-    ) -> Option<Texture> {
-        let texture = match Texture::from_image(image_path, alpha) {
-            Ok(t) => t,
-            Err(_) => return None,
-        };
-
-        self.textures.insert(name, texture)
+    /// Keeps a texture created some other way, e.g. from generated pixels, under `name`
+    pub fn add_texture(&mut self, texture: Texture, name: &str) {
+        self.textures.insert(name.to_string(), texture);
     }
 
     pub fn get_texture(&self, name: &str) -> Option<Texture> {
         self.textures.get(name).cloned()
     }
+
+    /// Packs and uploads the next atlas page, false once every image added for atlases is in one
+    fn build_atlas_page(&mut self) -> Result<bool, Error> {
+        match self.atlas_builder.build_page(max_atlas_size())? {
+            Some((texture, regions)) => {
+                self.atlases.push(texture);
+                self.regions.extend(regions);
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn get_region(&self, name: &str) -> Option<TextureRegion> {
+        self.regions.get(name).cloned()
+    }
+
+    /// Every cell of a sprite sheet sliced into a grid, in order
+    pub fn get_sheet_frames(&self, name: &str) -> Vec<TextureRegion> {
+        (0..)
+            .map_while(|index| self.get_region(&format!("{}/{}", name, index)))
+            .collect()
+    }
+
+    pub fn get_font(&self, name: &str) -> Option<&Font> {
+        self.fonts.get(name)
+    }
+
+    pub fn get_sound(&self, name: &str) -> Option<Sound> {
+        self.sounds.get(name).cloned()
+    }
+
+    pub fn get_music(&self, name: &str) -> Option<Music> {
+        self.music.get(name).cloned()
+    }
+
+    /// Paths of the levels in a pack listed by the manifest, in playing order
+    pub fn get_level_pack(&self, name: &str) -> Option<&[String]> {
+        self.level_packs.get(name).map(Vec::as_slice)
+    }
+}
+
+impl ResourceManager {
+    fn loader(&mut self) -> &mut AssetLoader {
+        self.loader
+            .get_or_insert_with(|| AssetLoader::new(&self.vfs))
+    }
+
+    /// Does what's left of loading an asset on the main thread
+    fn finish_decoded(&mut self, decoded: Decoded) -> Result<(), Error> {
+        match decoded {
+            Decoded::Texture {
+                name,
+                image,
+                options,
+            } => self.add_texture(Texture::from_decoded(&image, &options), &name),
+            Decoded::AtlasImage { name, image } => self.atlas_builder.add(&name, image),
+            Decoded::SpriteSheet {
+                name,
+                path,
+                image,
+                slices,
+            } => self
+                .atlas_builder
+                .add_sliced(&path, image, slices)
+                .map_err(in_entry("Sprite sheet", &name))?,
+            Decoded::Font { name, font } => {
+                let font = font
+                    .upload(max_atlas_size())
+                    .map_err(in_entry("Font", &name))?;
+                self.fonts.insert(name, font);
+            }
+            Decoded::Sound { name, sound } => {
+                self.sounds.insert(name, sound);
+            }
+            Decoded::Music { name, music } => {
+                self.music.insert(name, music);
+            }
+            Decoded::Data { path, data } => {
+                self.data.insert(path, data);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn decode_image(vfs: &Vfs, image_path: &str) -> Result<DynamicImage, Error> {
+    image::load_from_memory(&vfs.read(image_path)?).map_err(|source| Error::Image {
+        path: Some(image_path.to_string()),
+        source,
+    })
+}
+
+/// Wraps an error into the manifest entry it came from
+fn in_entry<'a>(kind: &'static str, name: &'a str) -> impl FnOnce(Error) -> Error + 'a {
+    move |e| e.in_entry(kind, name)
+}
+
+fn max_atlas_size() -> u32 {
+    let mut max_texture_size: GLint = 0;
+
+    unsafe {
+        gl_call!(gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_texture_size));
+    }
+
+    // Needs the GL context to be current. Every GL 3.3 implementation supports at least 1024,
+    // a driver reporting less is not trusted
+    (max_texture_size.max(1024) as u32).min(MAX_ATLAS_SIZE)
 }
@@ -1,185 +1,242 @@
-use std::{ffi::CString, fs::read_to_string};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    rc::Rc,
+};
 
 use gl::types::*;
 
-#[derive(Clone, Copy)]
+use super::uniform::{self, UniformInfo, UniformValue};
+use crate::engine::{
+    error::{Error, ShaderStage},
+    vfs::Vfs,
+};
+
+struct Program {
+    id: GLuint,
+    uniforms: HashMap<String, UniformInfo>,
+    /// Names already reported as missing, so a bad name set every frame is only reported once
+    reported: HashSet<String>,
+}
+
+/// A linked program together with the locations of its active uniforms, looked up once at link time.
+/// Clones share the program, so when it's replaced (e.g. hot-reloaded) every clone uses the new one.
+#[derive(Clone)]
 pub struct Shader {
-    program_id: GLuint,
+    program: Rc<RefCell<Program>>,
 }
 
 impl Shader {
     pub fn from_source(
+        vfs: &Vfs,
         vertex_shader_path: &str,
         fragment_shader_path: &str,
         geometry_shader_path: Option<&str>,
-    ) -> Result<Shader, String> {
-        let vertex_shader_id = compile_shader(vertex_shader_path, gl::VERTEX_SHADER)?;
-        let fragment_shader_id = compile_shader(fragment_shader_path, gl::FRAGMENT_SHADER)?;
+    ) -> Result<Shader, Error> {
+        // Shader objects are deleted once they go out of scope, whether linking worked or not
+        let vertex_shader = compile_shader(vfs, vertex_shader_path, ShaderStage::Vertex)?;
+        let fragment_shader = compile_shader(vfs, fragment_shader_path, ShaderStage::Fragment)?;
 
-        let geometry_shader_id = match geometry_shader_path {
-            Some(path) => Some(compile_shader(path, gl::GEOMETRY_SHADER)?),
+        let geometry_shader = match geometry_shader_path {
+            Some(path) => Some(compile_shader(vfs, path, ShaderStage::Geometry)?),
             None => None,
         };
 
-        let program_id = link_shaders(vertex_shader_id, fragment_shader_id, geometry_shader_id)?;
+        let program_id = link_shaders(&vertex_shader, &fragment_shader, geometry_shader.as_ref())?;
 
-        Ok(Self { program_id })
+        Ok(Self {
+            program: Rc::new(RefCell::new(Program {
+                id: program_id,
+                uniforms: uniform::introspect(program_id),
+                reported: HashSet::new(),
+            })),
+        })
     }
 
-    pub fn to_use(&self) {
-        unsafe {
-            gl::UseProgram(self.program_id);
-        }
-    }
+    /// Swaps in the program of `other` for this shader and all of its clones, the old program
+    /// is deleted unless `other` is still shared
+    pub fn replace_with(&self, other: Shader) {
+        let mut program = self.program.borrow_mut();
+        let mut other = other.program.borrow_mut();
 
-    pub fn set_bool(&self, name: &str, value: bool) {
-        let name = CString::new(name).unwrap();
+        std::mem::swap(&mut *program, &mut *other);
+    }
 
+    pub fn to_use(&self) {
         unsafe {
-            let location = gl::GetUniformLocation(self.program_id, name.as_ptr());
-            gl::Uniform1i(location, value as i32);
+            gl_call!(gl::UseProgram(self.program.borrow().id));
         }
     }
 
-    pub fn set_int(&self, name: &str, value: i32) {
-        let name = CString::new(name).unwrap();
-
-        unsafe {
-            let location = gl::GetUniformLocation(self.program_id, name.as_ptr());
-            gl::Uniform1i(location, value);
+    /// Sets a uniform of this program, the program has to be in use. Values not matching the
+    /// declared GLSL type are skipped, as OpenGL would reject them anyway. In debug builds those and
+    /// unknown names are reported, keep in mind the GLSL compiler strips uniforms which don't
+    /// contribute to the output.
+    pub fn set<T: UniformValue + ?Sized>(&self, name: &str, value: &T) {
+        let mut program = self.program.borrow_mut();
+
+        match program.uniforms.get(name) {
+            Some(info) if T::accepts(info.gl_type) => value.upload(info.location),
+            Some(_) => {
+                if cfg!(debug_assertions) {
+                    program.report(name, "value doesn't match the type declared in the shader");
+                }
+            }
+            None => {
+                if cfg!(debug_assertions) {
+                    program.report(name, "no active uniform with this name");
+                }
+            }
         }
     }
+}
 
-    pub fn set_float(&self, name: &str, value: f32) {
-        let name = CString::new(name).unwrap();
-
-        unsafe {
-            let location = gl::GetUniformLocation(self.program_id, name.as_ptr());
-            gl::Uniform1f(location, value);
+impl Program {
+    fn report(&mut self, name: &str, problem: &str) {
+        if self.reported.insert(name.to_string()) {
+            eprintln!(
+                "Uniform '{}' of shader program {}: {}",
+                name, self.id, problem
+            );
         }
     }
+}
 
-    pub fn set_mat4(&self, name: &str, value: *const f32) {
-        let name = CString::new(name).unwrap();
-
+impl Drop for Program {
+    fn drop(&mut self) {
         unsafe {
-            let location = gl::GetUniformLocation(self.program_id, name.as_ptr());
-            gl::UniformMatrix4fv(location, 1, gl::FALSE, value);
+            gl_call!(gl::DeleteProgram(self.id));
         }
     }
+}
 
-    pub fn set_vec3(&self, name: &str, value: glam::Vec3) {
-        let name = CString::new(name).unwrap();
+/// A compiled shader stage, deleted when dropped. Deleting it once it's attached to a linked
+/// program is fine, GL keeps it around for as long as the program exists
+struct ShaderObject {
+    id: GLuint,
+}
 
+impl Drop for ShaderObject {
+    fn drop(&mut self) {
         unsafe {
-            let location = gl::GetUniformLocation(self.program_id, name.as_ptr());
-            gl::Uniform3f(location, value.x, value.y, value.z);
+            gl_call!(gl::DeleteShader(self.id));
         }
     }
 }
 
-fn compile_shader(path: &str, kind: GLuint) -> Result<GLuint, String> {
-    let source = match read_to_string(path) {
-        Ok(s) => match CString::new(s) {
-            Ok(c_str) => c_str,
-            Err(_) => {
-                return Err(String::from(
-                    "Couldn't convert source to 0 terminated String (CString)",
-                ))
-            }
-        },
-        Err(_e) => return Err(String::from("Couldn't open the shader source")),
+fn compile_shader(vfs: &Vfs, path: &str, stage: ShaderStage) -> Result<ShaderObject, Error> {
+    let source = match CString::new(vfs.read_to_string(path)?) {
+        Ok(c_str) => c_str,
+        Err(_) => {
+            return Err(Error::invalid_asset(
+                path,
+                "Couldn't convert source to 0 terminated String (CString)",
+            ))
+        }
+    };
+
+    let kind = match stage {
+        ShaderStage::Vertex => gl::VERTEX_SHADER,
+        ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+        ShaderStage::Geometry => gl::GEOMETRY_SHADER,
     };
 
-    let shader_id = unsafe { gl::CreateShader(kind) };
+    let shader = ShaderObject {
+        id: unsafe { gl_call!(gl::CreateShader(kind)) },
+    };
+    let shader_id = shader.id;
 
     unsafe {
-        gl::ShaderSource(shader_id, 1, &source.as_ptr(), std::ptr::null());
-        gl::CompileShader(shader_id);
+        gl_call!(gl::ShaderSource(
+            shader_id,
+            1,
+            &source.as_ptr(),
+            std::ptr::null()
+        ));
+        gl_call!(gl::CompileShader(shader_id));
     }
 
     let mut success: GLint = 1;
 
     unsafe {
-        gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
+        gl_call!(gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success));
     }
 
     if success == 0 {
         let mut len: GLint = 0;
 
         unsafe {
-            gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut len);
+            gl_call!(gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut len));
         }
 
         let error = create_whitespace_cstring_with_len(len as usize);
 
         unsafe {
-            gl::GetShaderInfoLog(
+            gl_call!(gl::GetShaderInfoLog(
                 shader_id,
                 len,
                 std::ptr::null_mut(),
                 error.as_ptr() as *mut GLchar,
-            );
+            ));
         }
 
-        return Err(error.to_string_lossy().into_owned());
+        return Err(Error::ShaderCompile {
+            stage,
+            path: path.to_string(),
+            log: error.to_string_lossy().trim_end().to_string(),
+        });
     }
 
-    Ok(shader_id)
+    Ok(shader)
 }
 
 fn link_shaders(
-    vertex_shader_id: GLuint,
-    fragment_shader_id: GLuint,
-    geometry_shader_id: Option<GLuint>,
-) -> Result<GLuint, String> {
-    let program_id = unsafe { gl::CreateProgram() };
+    vertex_shader: &ShaderObject,
+    fragment_shader: &ShaderObject,
+    geometry_shader: Option<&ShaderObject>,
+) -> Result<GLuint, Error> {
+    let program_id = unsafe { gl_call!(gl::CreateProgram()) };
 
     unsafe {
-        gl::AttachShader(program_id, vertex_shader_id);
-        gl::AttachShader(program_id, fragment_shader_id);
+        gl_call!(gl::AttachShader(program_id, vertex_shader.id));
+        gl_call!(gl::AttachShader(program_id, fragment_shader.id));
 
-        if let Some(geomatry_shader_id) = geometry_shader_id {
-            gl::AttachShader(program_id, geomatry_shader_id)
+        if let Some(geomatry_shader) = geometry_shader {
+            gl_call!(gl::AttachShader(program_id, geomatry_shader.id))
         }
     }
 
-    unsafe { gl::LinkProgram(program_id) }
+    unsafe { gl_call!(gl::LinkProgram(program_id)) }
 
     let mut success: GLint = 1;
 
     unsafe {
-        gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+        gl_call!(gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success));
     }
 
     if success == 0 {
         let mut len: GLint = 0;
 
         unsafe {
-            gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut len);
+            gl_call!(gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut len));
         }
 
         let error = create_whitespace_cstring_with_len(len as usize);
 
         unsafe {
-            gl::GetProgramInfoLog(
+            gl_call!(gl::GetProgramInfoLog(
                 program_id,
                 len,
                 std::ptr::null_mut(),
                 error.as_ptr() as *mut GLchar,
-            );
+            ));
+            gl_call!(gl::DeleteProgram(program_id));
         }
 
-        return Err(error.to_string_lossy().into_owned());
-    }
-
-    unsafe {
-        gl::DeleteShader(vertex_shader_id);
-        gl::DeleteShader(fragment_shader_id);
-
-        if let Some(geomatry_shader_id) = geometry_shader_id {
-            gl::DeleteShader(geomatry_shader_id)
-        }
+        return Err(Error::ShaderLink {
+            log: error.to_string_lossy().trim_end().to_string(),
+        });
     }
 
     Ok(program_id)
@@ -0,0 +1,235 @@
+use std::{
+    io::{Read, Seek},
+    path::Path,
+    sync::Arc,
+};
+
+use lewton::inside_ogg::OggStreamReader;
+
+use crate::engine::{
+    error::Error,
+    vfs::{Vfs, VfsFile},
+};
+
+/// A sound effect decoded into memory, cheap to clone as the samples are shared
+#[derive(Clone)]
+pub struct Sound {
+    /// Interleaved stereo samples
+    samples: Arc<[f32]>,
+    sample_rate: u32,
+}
+
+impl Sound {
+    /// Decodes a whole WAV or OGG Vorbis file, mono files are turned into stereo
+    pub fn from_file(vfs: &Vfs, path: &str) -> Result<Self, Error> {
+        let mut stream = open_stream(&vfs.find(path)?, path)?;
+        let mut samples = vec![];
+
+        while stream.read(&mut samples)? > 0 {}
+
+        Ok(Self {
+            samples: samples.into(),
+            sample_rate: stream.sample_rate(),
+        })
+    }
+
+    pub fn samples(&self) -> &Arc<[f32]> {
+        &self.samples
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// A piece of music, only its location is kept as music is decoded while it plays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Music {
+    path: String,
+    file: VfsFile,
+}
+
+impl Music {
+    /// Checks the file can be decoded without reading more than its header
+    pub fn from_file(vfs: &Vfs, path: &str) -> Result<Self, Error> {
+        let file = vfs.find(path)?;
+        open_stream(&file, path)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            file,
+        })
+    }
+
+    /// Opens a new stream positioned at the start of the music
+    pub fn open(&self) -> Result<Box<dyn SampleStream>, Error> {
+        open_stream(&self.file, &self.path)
+    }
+}
+
+/// Incrementally decodes audio into interleaved stereo samples
+pub trait SampleStream: Send {
+    fn sample_rate(&self) -> u32;
+
+    /// Appends the next decoded samples to `out`, returns how many stereo frames were added
+    /// which is 0 once the end is reached
+    fn read(&mut self, out: &mut Vec<f32>) -> Result<usize, Error>;
+
+    /// Goes back to the start
+    fn rewind(&mut self) -> Result<(), Error>;
+}
+
+/// Opens a stream picking the decoder by the extension of `path`, the name `file` was found under
+pub fn open_stream(file: &VfsFile, path: &str) -> Result<Box<dyn SampleStream>, Error> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let reader = file.open(path)?;
+
+    let stream: Box<dyn SampleStream> = match extension.as_deref() {
+        Some("wav") => Box::new(WavStream::new(reader, path)?),
+        Some("ogg") => Box::new(OggStream::new(reader, path)?),
+        _ => return Err(Error::invalid_asset(path, "unsupported audio format")),
+    };
+
+    Ok(stream)
+}
+
+/// Frames decoded per read from a WAV file
+const WAV_CHUNK_FRAMES: usize = 4096;
+
+struct WavStream<R: Read + Seek> {
+    reader: hound::WavReader<R>,
+    path: String,
+    channels: usize,
+    /// Scales integer samples into -1.0..1.0, 0 for float samples
+    scale: f32,
+}
+
+impl<R: Read + Seek + Send> WavStream<R> {
+    fn new(reader: R, path: &str) -> Result<Self, Error> {
+        let reader = hound::WavReader::new(reader).map_err(|e| Error::invalid_asset(path, e))?;
+        let spec = reader.spec();
+
+        let scale = match spec.sample_format {
+            hound::SampleFormat::Int => 1.0 / (1u32 << (spec.bits_per_sample - 1)) as f32,
+            hound::SampleFormat::Float => 0.0,
+        };
+
+        Ok(Self {
+            reader,
+            path: path.to_string(),
+            channels: spec.channels.max(1) as usize,
+            scale,
+        })
+    }
+}
+
+impl<R: Read + Seek + Send> SampleStream for WavStream<R> {
+    fn sample_rate(&self) -> u32 {
+        self.reader.spec().sample_rate
+    }
+
+    fn read(&mut self, out: &mut Vec<f32>) -> Result<usize, Error> {
+        let count = WAV_CHUNK_FRAMES * self.channels;
+        let mut frame = Vec::with_capacity(self.channels);
+        let mut frames = 0;
+
+        let samples: Vec<f32> = if self.scale == 0.0 {
+            self.reader
+                .samples::<f32>()
+                .take(count)
+                .collect::<Result<_, _>>()
+        } else {
+            let scale = self.scale;
+            self.reader
+                .samples::<i32>()
+                .take(count)
+                .map(|sample| sample.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()
+        }
+        .map_err(|e| Error::invalid_asset(&self.path, e))?;
+
+        for sample in samples {
+            frame.push(sample);
+
+            if frame.len() == self.channels {
+                push_stereo(out, &frame);
+                frame.clear();
+                frames += 1;
+            }
+        }
+
+        Ok(frames)
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        self.reader.seek(0).map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+struct OggStream<R: Read + Seek> {
+    reader: OggStreamReader<R>,
+    path: String,
+}
+
+impl<R: Read + Seek + Send> OggStream<R> {
+    fn new(reader: R, path: &str) -> Result<Self, Error> {
+        let reader = OggStreamReader::new(reader).map_err(|e| Error::invalid_asset(path, e))?;
+
+        Ok(Self {
+            reader,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl<R: Read + Seek + Send> SampleStream for OggStream<R> {
+    fn sample_rate(&self) -> u32 {
+        self.reader.ident_hdr.audio_sample_rate
+    }
+
+    fn read(&mut self, out: &mut Vec<f32>) -> Result<usize, Error> {
+        let channels = self.reader.ident_hdr.audio_channels.max(1) as usize;
+
+        // Packets can decode to nothing, keep going until there's something or the stream ends
+        loop {
+            let packet = self
+                .reader
+                .read_dec_packet_itl()
+                .map_err(|e| Error::invalid_asset(&self.path, e))?;
+
+            match packet {
+                Some(samples) if samples.is_empty() => continue,
+                Some(samples) => {
+                    let mut frame = Vec::with_capacity(channels);
+
+                    for chunk in samples.chunks_exact(channels) {
+                        frame.clear();
+                        frame.extend(chunk.iter().map(|&s| s as f32 / 32768.0));
+                        push_stereo(out, &frame);
+                    }
+
+                    return Ok(samples.len() / channels);
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        self.reader
+            .seek_absgp_pg(0)
+            .map_err(|e| Error::invalid_asset(&self.path, e))
+    }
+}
+
+/// Mono is played on both sides, anything past the first two channels is dropped
+fn push_stereo(out: &mut Vec<f32>, frame: &[f32]) {
+    match frame {
+        [mono] => out.extend([*mono, *mono]),
+        [left, right, ..] => out.extend([*left, *right]),
+        [] => (),
+    }
+}
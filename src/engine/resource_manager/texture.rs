@@ -1,52 +1,367 @@
+use std::rc::Rc;
+
 use gl::types::*;
+use image::{DynamicImage, RgbaImage};
+use serde::Deserialize;
+
+use crate::engine::gl_object::{GlObject, SharedGlObject, TextureKind};
+
+/// What happens to texture coordinates outside of 0..1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// Blocky, for pixel art
+    Nearest,
+    Linear,
+}
 
-#[derive(Debug, Clone, Copy)]
+/// How a texture is stored and sampled, also readable from the fields of a manifest entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TextureOptions {
+    pub wrap: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// Generates mipmaps, which `min_filter` then also blends between
+    pub mipmaps: bool,
+    /// Colors are stored as sRGB and turned linear when sampled, which only looks right
+    /// when drawing into an sRGB framebuffer
+    pub srgb: bool,
+    /// Multiplies colors by their alpha when loading, for premultiplied alpha blending
+    pub premultiply_alpha: bool,
+    /// Keeps the alpha channel of images which have one, otherwise it's dropped
+    pub alpha: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            wrap: Wrap::Repeat,
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmaps: false,
+            srgb: false,
+            premultiply_alpha: false,
+            alpha: true,
+        }
+    }
+}
+
+/// Atlases are clamped so neighbouring regions don't bleed into each other's edges
+const ATLAS_OPTIONS: TextureOptions = TextureOptions {
+    wrap: Wrap::ClampToEdge,
+    min_filter: Filter::Linear,
+    mag_filter: Filter::Linear,
+    mipmaps: false,
+    srgb: false,
+    premultiply_alpha: false,
+    alpha: true,
+};
+
+/// A 2D texture, clones share it and it's deleted together with the last one
+#[derive(Debug, Clone)]
 pub struct Texture {
-    id: GLuint,
+    object: SharedGlObject<TextureKind>,
+    /// Whether its colors were multiplied by their alpha when loading
+    premultiplied: bool,
 }
 
 impl Texture {
-    pub fn from_image(image_path: &str, alpha: bool) -> Result<Self, String> {
-        let format = if alpha { gl::RGBA } else { gl::RGB };
+    /// Uploads any decoded image, grayscale, 16 bit, float and palette images included, by
+    /// converting it to 8 bit RGB or RGBA depending on whether it has alpha to keep
+    pub fn from_decoded(image: &DynamicImage, options: &TextureOptions) -> Self {
+        let pixels = normalize(image, options);
 
-        let image = match image::open(image_path) {
-            Ok(i) => i,
-            Err(e) => return Err(e.to_string()),
-        };
+        upload(
+            &pixels.data,
+            pixels.width,
+            pixels.height,
+            pixels.format,
+            pixels.internal_format,
+            options,
+        )
+    }
 
-        let mut texture: GLuint = 0;
+    /// Uploads tightly packed 8 bit RGBA pixels, edges are clamped as these are usually atlases
+    pub fn from_rgba(width: u32, height: u32, pixels: &[u8]) -> Self {
+        debug_assert_eq!(pixels.len(), (width * height * 4) as usize);
 
-        unsafe {
-            // Create texture
-            gl::GenTextures(1, &mut texture);
-            // Bind texture
-            gl::BindTexture(gl::TEXTURE_2D, texture);
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                format as GLint,
-                image.width() as GLsizei,
-                image.height() as GLsizei,
-                0,
-                format,
-                gl::UNSIGNED_BYTE,
-                image.as_bytes().as_ptr() as *const _,
-            );
-            // Set texture wrap and filter modes
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
-            // Unbind the current texture, will be rebind whenever we want to draw to the screen
-            gl::BindTexture(gl::TEXTURE_2D, 0);
-        }
-
-        Ok(Self { id: texture })
+        upload(pixels, width, height, gl::RGBA, gl::RGBA8, &ATLAS_OPTIONS)
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.object.id()
+    }
+
+    /// Whether it has to be drawn with premultiplied alpha blending
+    pub fn is_premultiplied(&self) -> bool {
+        self.premultiplied
     }
 
     pub fn bind(&self) {
         unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl_call!(gl::BindTexture(gl::TEXTURE_2D, self.id()));
+        }
+    }
+}
+
+impl Wrap {
+    fn gl(self) -> GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
         }
     }
 }
+
+impl Filter {
+    fn gl(self, mipmaps: bool) -> GLenum {
+        match (self, mipmaps) {
+            (Filter::Nearest, false) => gl::NEAREST,
+            (Filter::Linear, false) => gl::LINEAR,
+            (Filter::Nearest, true) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, true) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+fn upload(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    format: GLenum,
+    internal_format: GLenum,
+    options: &TextureOptions,
+) -> Texture {
+    let texture = GlObject::<TextureKind>::new();
+
+    unsafe {
+        gl_call!(gl::BindTexture(gl::TEXTURE_2D, texture.id()));
+        // Rows of RGB pixels aren't necessarily a multiple of the default 4 bytes long
+        gl_call!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1));
+        gl_call!(gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            internal_format as GLint,
+            width as GLsizei,
+            height as GLsizei,
+            0,
+            format,
+            gl::UNSIGNED_BYTE,
+            pixels.as_ptr() as *const _,
+        ));
+        gl_call!(gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4));
+
+        if options.mipmaps {
+            gl_call!(gl::GenerateMipmap(gl::TEXTURE_2D));
+        }
+
+        gl_call!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_S,
+            options.wrap.gl() as GLint
+        ));
+        gl_call!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_T,
+            options.wrap.gl() as GLint
+        ));
+        gl_call!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_MIN_FILTER,
+            options.min_filter.gl(options.mipmaps) as GLint
+        ));
+        // Magnification never uses mipmaps
+        gl_call!(gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_MAG_FILTER,
+            options.mag_filter.gl(false) as GLint
+        ));
+        // Unbind the current texture, will be rebind whenever we want to draw to the screen
+        gl_call!(gl::BindTexture(gl::TEXTURE_2D, 0));
+    }
+
+    Texture {
+        object: Rc::new(texture),
+        premultiplied: options.premultiply_alpha && format == gl::RGBA,
+    }
+}
+
+/// 8 bit pixels in the layout `glTexImage2D` is told about
+struct Pixels {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: GLenum,
+    internal_format: GLenum,
+}
+
+/// Converts `image` to 8 bit RGBA if it has alpha to keep and to 8 bit RGB otherwise
+fn normalize(image: &DynamicImage, options: &TextureOptions) -> Pixels {
+    if options.alpha && image.color().has_alpha() {
+        let mut pixels = image.to_rgba8();
+
+        if options.premultiply_alpha {
+            premultiply(&mut pixels);
+        }
+
+        Pixels {
+            width: pixels.width(),
+            height: pixels.height(),
+            data: pixels.into_raw(),
+            format: gl::RGBA,
+            internal_format: if options.srgb {
+                gl::SRGB8_ALPHA8
+            } else {
+                gl::RGBA8
+            },
+        }
+    } else {
+        let pixels = image.to_rgb8();
+
+        Pixels {
+            width: pixels.width(),
+            height: pixels.height(),
+            data: pixels.into_raw(),
+            format: gl::RGB,
+            internal_format: if options.srgb { gl::SRGB8 } else { gl::RGB8 },
+        }
+    }
+}
+
+fn premultiply(pixels: &mut RgbaImage) {
+    for pixel in pixels.pixels_mut() {
+        let alpha = pixel[3] as u32;
+
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, LumaA, Rgb, Rgba};
+
+    use super::*;
+
+    fn rgba(pixel: [u8; 4]) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_pixel(2, 1, Rgba(pixel)))
+    }
+
+    #[test]
+    fn keeps_alpha_as_rgba8() {
+        let pixels = normalize(&rgba([10, 20, 30, 40]), &TextureOptions::default());
+
+        assert_eq!((pixels.width, pixels.height), (2, 1));
+        assert_eq!(
+            (pixels.format, pixels.internal_format),
+            (gl::RGBA, gl::RGBA8)
+        );
+        assert_eq!(pixels.data, [10, 20, 30, 40, 10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn drops_alpha_when_asked_to() {
+        let options = TextureOptions {
+            alpha: false,
+            ..Default::default()
+        };
+        let pixels = normalize(&rgba([10, 20, 30, 40]), &options);
+
+        assert_eq!((pixels.format, pixels.internal_format), (gl::RGB, gl::RGB8));
+        assert_eq!(pixels.data, [10, 20, 30, 10, 20, 30]);
+    }
+
+    #[test]
+    fn images_without_alpha_stay_rgb8() {
+        let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(1, 1, Rgb([1, 2, 3])));
+        let pixels = normalize(&image, &TextureOptions::default());
+
+        assert_eq!((pixels.format, pixels.internal_format), (gl::RGB, gl::RGB8));
+        assert_eq!(pixels.data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn converts_16_bit_grayscale() {
+        let image = DynamicImage::ImageLumaA16(ImageBuffer::from_pixel(1, 1, LumaA([0xffff, 0])));
+        let pixels = normalize(&image, &TextureOptions::default());
+
+        assert_eq!(pixels.format, gl::RGBA);
+        assert_eq!(pixels.data, [255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn srgb_picks_srgb_formats() {
+        let options = TextureOptions {
+            srgb: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            normalize(&rgba([0; 4]), &options).internal_format,
+            gl::SRGB8_ALPHA8
+        );
+        assert_eq!(
+            normalize(
+                &rgba([0; 4]),
+                &TextureOptions {
+                    alpha: false,
+                    ..options
+                }
+            )
+            .internal_format,
+            gl::SRGB8
+        );
+    }
+
+    #[test]
+    fn premultiplies_colors_by_alpha() {
+        let mut pixels = RgbaImage::from_vec(
+            3,
+            1,
+            vec![200, 100, 50, 128, 200, 100, 50, 255, 200, 100, 50, 0],
+        )
+        .unwrap();
+
+        premultiply(&mut pixels);
+
+        assert_eq!(
+            pixels.into_raw(),
+            [100, 50, 25, 128, 200, 100, 50, 255, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn premultiplies_only_kept_alpha() {
+        let options = TextureOptions {
+            premultiply_alpha: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            normalize(&rgba([200, 100, 50, 128]), &options).data[..4],
+            [100, 50, 25, 128]
+        );
+        assert_eq!(
+            normalize(
+                &rgba([200, 100, 50, 128]),
+                &TextureOptions {
+                    alpha: false,
+                    ..options
+                }
+            )
+            .data[..3],
+            [200, 100, 50]
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use gl::types::*;
+
+/// An active uniform of a linked program as reported by OpenGL
+#[derive(Debug, Clone, Copy)]
+pub struct UniformInfo {
+    pub location: GLint,
+    pub gl_type: GLenum,
+}
+
+/// Texture unit a sampler uniform reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sampler(pub u32);
+
+/// Values which can be uploaded to a uniform, implemented for scalars, glam vectors and
+/// matrices, samplers and slices of those for uniform arrays
+pub trait UniformValue {
+    /// Whether a uniform declared with the given GLSL type can hold this value
+    fn accepts(gl_type: GLenum) -> bool;
+
+    /// Uploads the value to the currently bound program
+    fn upload(&self, location: GLint);
+}
+
+impl UniformValue for bool {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::BOOL || gl_type == gl::INT
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe { gl_call!(gl::Uniform1i(location, *self as GLint)) }
+    }
+}
+
+impl UniformValue for i32 {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::INT || gl_type == gl::BOOL
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe { gl_call!(gl::Uniform1i(location, *self)) }
+    }
+}
+
+impl UniformValue for f32 {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::FLOAT
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe { gl_call!(gl::Uniform1f(location, *self)) }
+    }
+}
+
+impl UniformValue for glam::Vec2 {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::FLOAT_VEC2
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe { gl_call!(gl::Uniform2f(location, self.x, self.y)) }
+    }
+}
+
+impl UniformValue for glam::Vec3 {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::FLOAT_VEC3
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe { gl_call!(gl::Uniform3f(location, self.x, self.y, self.z)) }
+    }
+}
+
+impl UniformValue for glam::Vec4 {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::FLOAT_VEC4
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe { gl_call!(gl::Uniform4f(location, self.x, self.y, self.z, self.w)) }
+    }
+}
+
+impl UniformValue for glam::Mat4 {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::FLOAT_MAT4
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl_call!(gl::UniformMatrix4fv(
+                location,
+                1,
+                gl::FALSE,
+                self.as_ref().as_ptr()
+            ))
+        }
+    }
+}
+
+impl UniformValue for Sampler {
+    fn accepts(gl_type: GLenum) -> bool {
+        matches!(
+            gl_type,
+            gl::SAMPLER_2D | gl::SAMPLER_2D_MULTISAMPLE | gl::SAMPLER_2D_ARRAY | gl::SAMPLER_CUBE
+        )
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe { gl_call!(gl::Uniform1i(location, self.0 as GLint)) }
+    }
+}
+
+impl UniformValue for [i32] {
+    fn accepts(gl_type: GLenum) -> bool {
+        i32::accepts(gl_type)
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl_call!(gl::Uniform1iv(
+                location,
+                self.len() as GLsizei,
+                self.as_ptr()
+            ))
+        }
+    }
+}
+
+impl UniformValue for [f32] {
+    fn accepts(gl_type: GLenum) -> bool {
+        f32::accepts(gl_type)
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl_call!(gl::Uniform1fv(
+                location,
+                self.len() as GLsizei,
+                self.as_ptr()
+            ))
+        }
+    }
+}
+
+// glam vectors and matrices are plain arrays of f32 so a slice of them can be passed as is
+
+impl UniformValue for [glam::Vec2] {
+    fn accepts(gl_type: GLenum) -> bool {
+        glam::Vec2::accepts(gl_type)
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl_call!(gl::Uniform2fv(
+                location,
+                self.len() as GLsizei,
+                self.as_ptr() as *const f32
+            ))
+        }
+    }
+}
+
+impl UniformValue for [glam::Vec3] {
+    fn accepts(gl_type: GLenum) -> bool {
+        glam::Vec3::accepts(gl_type)
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl_call!(gl::Uniform3fv(
+                location,
+                self.len() as GLsizei,
+                self.as_ptr() as *const f32
+            ))
+        }
+    }
+}
+
+impl UniformValue for [glam::Vec4] {
+    fn accepts(gl_type: GLenum) -> bool {
+        glam::Vec4::accepts(gl_type)
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl_call!(gl::Uniform4fv(
+                location,
+                self.len() as GLsizei,
+                self.as_ptr() as *const f32
+            ))
+        }
+    }
+}
+
+impl UniformValue for [glam::Mat4] {
+    fn accepts(gl_type: GLenum) -> bool {
+        glam::Mat4::accepts(gl_type)
+    }
+
+    fn upload(&self, location: GLint) {
+        unsafe {
+            gl_call!(gl::UniformMatrix4fv(
+                location,
+                self.len() as GLsizei,
+                gl::FALSE,
+                self.as_ptr() as *const f32,
+            ))
+        }
+    }
+}
+
+impl<T: UniformValue, const N: usize> UniformValue for [T; N]
+where
+    [T]: UniformValue,
+{
+    fn accepts(gl_type: GLenum) -> bool {
+        <[T]>::accepts(gl_type)
+    }
+
+    fn upload(&self, location: GLint) {
+        self.as_slice().upload(location)
+    }
+}
+
+/// Queries every active uniform of a linked program. Arrays are reported by OpenGL as
+/// `name[0]`, they're stored under their plain name so they can be set as a whole.
+pub fn introspect(program_id: GLuint) -> HashMap<String, UniformInfo> {
+    let mut count: GLint = 0;
+    let mut max_name_length: GLint = 0;
+
+    unsafe {
+        gl_call!(gl::GetProgramiv(
+            program_id,
+            gl::ACTIVE_UNIFORMS,
+            &mut count
+        ));
+        gl_call!(gl::GetProgramiv(
+            program_id,
+            gl::ACTIVE_UNIFORM_MAX_LENGTH,
+            &mut max_name_length,
+        ));
+    }
+
+    let mut uniforms = HashMap::with_capacity(count.max(0) as usize);
+    let mut name_buffer = vec![0u8; max_name_length.max(1) as usize];
+
+    for index in 0..count.max(0) as GLuint {
+        let mut length: GLsizei = 0;
+        let mut size: GLint = 0;
+        let mut gl_type: GLenum = 0;
+
+        unsafe {
+            gl_call!(gl::GetActiveUniform(
+                program_id,
+                index,
+                name_buffer.len() as GLsizei,
+                &mut length,
+                &mut size,
+                &mut gl_type,
+                name_buffer.as_mut_ptr() as *mut GLchar,
+            ));
+        }
+
+        let full_name = String::from_utf8_lossy(&name_buffer[..length as usize]).into_owned();
+        let name = full_name
+            .strip_suffix("[0]")
+            .unwrap_or(&full_name)
+            .to_string();
+
+        let c_name = std::ffi::CString::new(full_name.as_str()).unwrap();
+        let location = unsafe { gl_call!(gl::GetUniformLocation(program_id, c_name.as_ptr())) };
+
+        // Uniforms inside of uniform blocks have no location and can't be set individually
+        if location >= 0 {
+            uniforms.insert(name, UniformInfo { location, gl_type });
+        }
+    }
+
+    uniforms
+}
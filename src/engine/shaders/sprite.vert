@@ -2,14 +2,19 @@
 
 
 layout (location = 0) in vec4 vertex; // <vec2 position, vec2 texCoord>
+layout (location = 1) in mat4 model; // per instance, takes up locations 1 to 4
+layout (location = 5) in vec4 color; // per instance
+layout (location = 6) in vec4 uv; // per instance, <vec2 top left, vec2 bottom right>
 
 out vec2 TexCoords;
+out vec4 SpriteColor;
 
-uniform mat4 model;
 uniform mat4 projection;
+uniform mat4 view;
 
 void main() 
 {
-    TexCoords = vertex.zw;
-    gl_Position = projection * model * vec4(vertex.xy, 0.0, 1.0);
+    TexCoords = mix(uv.xy, uv.zw, vertex.zw);
+    SpriteColor = color;
+    gl_Position = projection * view * model * vec4(vertex.xy, 0.0, 1.0);
 }
\ No newline at end of file
@@ -1,19 +1,129 @@
 use gl::types::*;
 
-use super::resource_manager::{shader::Shader, texture::Texture};
+use super::gl_object::{Buffer, VertexArray};
+use super::resource_manager::{
+    atlas::TextureRegion,
+    font::{Font, TextStyle},
+    shader::Shader,
+    texture::Texture,
+    uniform::Sampler,
+};
 
+/// Per instance data streamed to the GPU, the layout has to match the attributes of sprite.vert
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpriteInstance {
+    model: [f32; 16],
+    color: [f32; 4],
+    uv: [f32; 4],
+}
+
+/// How a sprite is combined with what was drawn before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BlendMode {
+    /// Regular transparency
+    Alpha,
+    /// Adds the sprite to the background, overlapping sprites glow
+    Additive,
+    /// Transparency for textures loaded with `premultiply_alpha`
+    Premultiplied,
+}
+
+impl BlendMode {
+    fn apply(self) {
+        unsafe {
+            match self {
+                BlendMode::Alpha => gl_call!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA)),
+                BlendMode::Additive => gl_call!(gl::BlendFunc(gl::SRC_ALPHA, gl::ONE)),
+                BlendMode::Premultiplied => {
+                    gl_call!(gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA))
+                }
+            }
+        }
+    }
+}
+
+/// Groups of sprites drawn on top of each other in this order, no matter in which order
+/// they were submitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    Background,
+    World,
+    Effects,
+    Hud,
+    /// Debugging aids drawn on top of everything
+    Overlay,
+}
+
+const LAYER_COUNT: usize = 5;
+
+/// Something which knows how to queue itself, submitted through `SpriteRenderer::submit`
+pub trait Drawable {
+    fn layer(&self) -> Layer {
+        Layer::World
+    }
+
+    /// Orders drawables within their layer, lower keys are drawn first
+    fn sort_key(&self) -> i32 {
+        0
+    }
+
+    /// `alpha` is the interpolation factor between the previous and the current simulation tick
+    fn draw(&self, sprite_renderer: &mut SpriteRenderer, alpha: f32);
+}
+
+struct QueuedSprite {
+    layer: Layer,
+    sort_key: i32,
+    texture: Texture,
+    blend: BlendMode,
+    instance: SpriteInstance,
+}
+
+impl QueuedSprite {
+    /// Sprites sharing all of these are drawn with a single instanced call
+    fn batch(&self) -> (Layer, i32, BlendMode, GLuint) {
+        (self.layer, self.sort_key, self.blend, self.texture.id())
+    }
+}
+
+const INITIAL_INSTANCE_CAPACITY: usize = 256;
+/// Line segments circle outlines are made of
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Collects sprites during the frame and draws them on `flush` as a layered render queue.
+/// Layers are drawn in order, within a layer sprites are sorted by their sort key and then
+/// batched into one instanced draw call per blend mode and texture. Sprites with the same
+/// layer and sort key only keep their submission order within the same blend mode and
+/// texture, alpha blended sprites are drawn before additive ones.
 pub struct SpriteRenderer {
     shader: Shader,
-    quad_vao: GLuint,
+    projection: glam::Mat4,
+    /// View transform of every layer, indexed by layer
+    views: [glam::Mat4; LAYER_COUNT],
+    hidden_layers: [bool; LAYER_COUNT],
+    /// Where sprites are queued to, changed by `set_layer` and `submit`
+    layer: Layer,
+    sort_key: i32,
+    quad_vao: VertexArray,
+    /// Only referenced by the vertex array, kept to be deleted with it
+    _quad_vbo: Buffer,
+    instance_vbo: Buffer,
+    /// 1x1 white pixel, tinted to draw shapes
+    white: Texture,
+    instance_capacity: usize,
+    queue: Vec<QueuedSprite>,
+    instances: Vec<SpriteInstance>,
 }
 
 impl SpriteRenderer {
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, shader: Shader) -> Self {
         let projection =
             glam::Mat4::orthographic_rh_gl(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
 
-        let mut quad_vao = 0;
-        let mut vbo = 0;
+        let quad_vao = VertexArray::new();
+        let quad_vbo = Buffer::new();
+        let instance_vbo = Buffer::new();
 
         let vertices: [f32; 24] = [
             // pos    // tex
@@ -22,52 +132,273 @@ impl SpriteRenderer {
         ];
 
         unsafe {
-            gl::GenVertexArrays(1, &mut quad_vao);
-            gl::GenBuffers(1, &mut vbo);
-
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, quad_vbo.id()));
+            gl_call!(gl::BufferData(
                 gl::ARRAY_BUFFER,
                 (std::mem::size_of::<f32>() * vertices.len()) as GLsizeiptr,
                 vertices.as_ptr() as *const _,
                 gl::STATIC_DRAW,
-            );
+            ));
 
-            gl::BindVertexArray(quad_vao);
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(
+            gl_call!(gl::BindVertexArray(quad_vao.id()));
+            gl_call!(gl::EnableVertexAttribArray(0));
+            gl_call!(gl::VertexAttribPointer(
                 0,
                 4,
                 gl::FLOAT,
                 gl::FALSE,
                 (4 * std::mem::size_of::<f32>()) as GLsizei,
                 std::ptr::null(),
-            )
+            ));
+
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, instance_vbo.id()));
+            gl_call!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (std::mem::size_of::<SpriteInstance>() * INITIAL_INSTANCE_CAPACITY) as GLsizeiptr,
+                std::ptr::null(),
+                gl::STREAM_DRAW,
+            ));
+
+            // mat4 model takes up 4 vec4 attributes (1 to 4), color is the 5th and uv the 6th
+            for location in 1..=6 {
+                gl_call!(gl::EnableVertexAttribArray(location));
+                gl_call!(gl::VertexAttribDivisor(location, 1));
+            }
+
+            gl_call!(gl::BindVertexArray(0));
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+        }
+
+        Self {
+            shader,
+            projection,
+            views: [glam::Mat4::IDENTITY; LAYER_COUNT],
+            hidden_layers: [false; LAYER_COUNT],
+            layer: Layer::World,
+            sort_key: 0,
+            quad_vao,
+            _quad_vbo: quad_vbo,
+            instance_vbo,
+            white: Texture::from_rgba(1, 1, &[255, 255, 255, 255]),
+            instance_capacity: INITIAL_INSTANCE_CAPACITY,
+            queue: vec![],
+            instances: vec![],
         }
+    }
 
-        let shader = Shader::from_source(
-            "./src/engine/shaders/sprite.vert",
-            "./src/engine/shaders/sprite.frag",
-            None,
-        )
-        .unwrap();
+    /// Transform from world to logical screen coordinates of a layer, usually
+    /// `Camera2D::view_matrix`, applied when the layer is flushed
+    pub fn set_view(&mut self, layer: Layer, view: glam::Mat4) {
+        self.views[layer as usize] = view;
+    }
+
+    /// Sprites queued from now on go to `layer` with the given sort key
+    pub fn set_layer(&mut self, layer: Layer, sort_key: i32) {
+        self.layer = layer;
+        self.sort_key = sort_key;
+    }
 
-        shader.to_use();
-        shader.set_int("image", 0);
-        shader.set_mat4("projection", &projection.to_cols_array()[0]);
+    /// Hidden layers drop whatever is queued to them, meant for debugging
+    pub fn set_layer_visible(&mut self, layer: Layer, visible: bool) {
+        self.hidden_layers[layer as usize] = !visible;
+    }
 
-        Self { quad_vao, shader }
+    pub fn is_layer_visible(&self, layer: Layer) -> bool {
+        !self.hidden_layers[layer as usize]
     }
 
+    pub fn toggle_layer(&mut self, layer: Layer) {
+        self.set_layer_visible(layer, !self.is_layer_visible(layer));
+    }
+
+    /// Queues a drawable to its own layer and sort key
+    pub fn submit(&mut self, drawable: &dyn Drawable, alpha: f32) {
+        let (layer, sort_key) = (self.layer, self.sort_key);
+
+        self.set_layer(drawable.layer(), drawable.sort_key());
+        drawable.draw(self, alpha);
+        self.set_layer(layer, sort_key);
+    }
+
+    /// Queues a whole texture, nothing is drawn until `flush` is called
     pub fn draw_sprite(
-        &self,
-        texture: Texture,
+        &mut self,
+        texture: &Texture,
         position: glam::Vec2,
         size: glam::Vec2,
         rotate: f32,
         color: glam::Vec3,
     ) {
-        self.shader.to_use();
+        self.queue_sprite(
+            texture,
+            glam::vec4(0.0, 0.0, 1.0, 1.0),
+            position,
+            size,
+            rotate,
+            color.extend(1.0),
+            if texture.is_premultiplied() {
+                BlendMode::Premultiplied
+            } else {
+                BlendMode::Alpha
+            },
+        );
+    }
+
+    /// Queues a region of a texture such as an atlas entry, nothing is drawn until `flush` is called
+    pub fn draw_region(
+        &mut self,
+        region: &TextureRegion,
+        position: glam::Vec2,
+        size: glam::Vec2,
+        rotate: f32,
+        color: glam::Vec3,
+    ) {
+        self.queue_sprite(
+            region.texture(),
+            region.uv(),
+            position,
+            size,
+            rotate,
+            color.extend(1.0),
+            BlendMode::Alpha,
+        );
+    }
+
+    /// Like `draw_region` but with a translucent color and a choice of blend mode
+    pub fn draw_region_blended(
+        &mut self,
+        region: &TextureRegion,
+        position: glam::Vec2,
+        size: glam::Vec2,
+        rotate: f32,
+        color: glam::Vec4,
+        blend: BlendMode,
+    ) {
+        self.queue_sprite(
+            region.texture(),
+            region.uv(),
+            position,
+            size,
+            rotate,
+            color,
+            blend,
+        );
+    }
+
+    /// Queues the glyphs of `text`, `position` is the top of the first line and the x
+    /// coordinate lines are aligned to
+    pub fn draw_text(&mut self, font: &Font, text: &str, position: glam::Vec2, style: &TextStyle) {
+        for glyph in font.layout(text, position, style) {
+            self.draw_region_blended(
+                &glyph.region,
+                glyph.position,
+                glyph.size,
+                0.0,
+                style.color,
+                BlendMode::Alpha,
+            );
+        }
+    }
+
+    /// Queues a solid rectangle
+    pub fn draw_rect(&mut self, position: glam::Vec2, size: glam::Vec2, color: glam::Vec4) {
+        self.queue_sprite(
+            &self.white.clone(),
+            glam::vec4(0.0, 0.0, 1.0, 1.0),
+            position,
+            size,
+            0.0,
+            color,
+            BlendMode::Alpha,
+        );
+    }
+
+    /// Queues the outline of a rectangle, the outline grows inwards so it never exceeds `size`
+    pub fn draw_rect_outline(
+        &mut self,
+        position: glam::Vec2,
+        size: glam::Vec2,
+        thickness: f32,
+        color: glam::Vec4,
+    ) {
+        let thickness = thickness.min(size.x / 2.0).min(size.y / 2.0);
+        let side = size.y - 2.0 * thickness;
+
+        self.draw_rect(position, glam::vec2(size.x, thickness), color);
+        self.draw_rect(
+            position + glam::vec2(0.0, size.y - thickness),
+            glam::vec2(size.x, thickness),
+            color,
+        );
+        self.draw_rect(
+            position + glam::vec2(0.0, thickness),
+            glam::vec2(thickness, side),
+            color,
+        );
+        self.draw_rect(
+            position + glam::vec2(size.x - thickness, thickness),
+            glam::vec2(thickness, side),
+            color,
+        );
+    }
+
+    /// Queues a line from `from` to `to`, `thickness` is spread evenly on both sides of it
+    pub fn draw_line(
+        &mut self,
+        from: glam::Vec2,
+        to: glam::Vec2,
+        thickness: f32,
+        color: glam::Vec4,
+    ) {
+        let direction = to - from;
+        let length = direction.length();
+        let size = glam::vec2(length, thickness);
+
+        // Sprites rotate around their center, so the quad is centered on the middle of the line
+        self.queue_sprite(
+            &self.white.clone(),
+            glam::vec4(0.0, 0.0, 1.0, 1.0),
+            (from + to) / 2.0 - size / 2.0,
+            size,
+            direction.y.atan2(direction.x).to_degrees(),
+            color,
+            BlendMode::Alpha,
+        );
+    }
+
+    /// Queues the outline of a circle made of straight segments
+    pub fn draw_circle_outline(
+        &mut self,
+        center: glam::Vec2,
+        radius: f32,
+        thickness: f32,
+        color: glam::Vec4,
+    ) {
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + glam::vec2(angle.cos(), angle.sin()) * radius
+        };
+
+        for i in 0..CIRCLE_SEGMENTS {
+            self.draw_line(point(i), point(i + 1), thickness, color);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn queue_sprite(
+        &mut self,
+        texture: &Texture,
+        uv: glam::Vec4,
+        position: glam::Vec2,
+        size: glam::Vec2,
+        rotate: f32,
+        color: glam::Vec4,
+        blend: BlendMode,
+    ) {
+        if self.hidden_layers[self.layer as usize] {
+            return;
+        }
+
         let model = glam::Mat4::IDENTITY
             * glam::Mat4::from_translation(glam::vec3(position.x, position.y, 0.0))
             * glam::Mat4::from_translation(glam::vec3(0.5 * size.x, 0.5 * size.y, 0.0))
@@ -75,19 +406,141 @@ impl SpriteRenderer {
             * glam::Mat4::from_translation(glam::vec3(-0.5 * size.x, -0.5 * size.y, 0.0))
             * glam::Mat4::from_scale(glam::vec3(size.x, size.y, 1.0));
 
-        self.shader.set_mat4("model", &model.to_cols_array()[0]);
-        self.shader.set_vec3("spriteColor", color);
+        self.queue.push(QueuedSprite {
+            layer: self.layer,
+            sort_key: self.sort_key,
+            texture: texture.clone(),
+            blend,
+            instance: SpriteInstance {
+                model: model.to_cols_array(),
+                color: color.to_array(),
+                uv: uv.to_array(),
+            },
+        });
+    }
+
+    /// Draws every queued sprite, layer by layer
+    pub fn flush(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+
+        // Stable sort, sprites sharing a batch keep the order they were submitted in
+        self.queue.sort_by_key(QueuedSprite::batch);
+
+        self.instances.clear();
+        self.instances
+            .extend(self.queue.iter().map(|sprite| sprite.instance));
+
+        self.upload_instances();
+
+        // Set every flush as the program may have been swapped by a hot-reload
+        self.shader.to_use();
+        self.shader.set("image", &Sampler(0));
+        self.shader.set("projection", &self.projection);
+
+        unsafe {
+            gl_call!(gl::ActiveTexture(gl::TEXTURE0));
+            gl_call!(gl::BindVertexArray(self.quad_vao.id()));
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_vbo.id()));
+        }
+
+        let mut start = 0;
+        let mut current_layer = None;
+
+        while start < self.queue.len() {
+            let first = &self.queue[start];
+            let (layer, blend) = (first.layer, first.blend);
+            let batch = first.batch();
+            let count = self.queue[start..]
+                .iter()
+                .take_while(|sprite| sprite.batch() == batch)
+                .count();
+
+            if current_layer != Some(layer) {
+                self.shader.set("view", &self.views[layer as usize]);
+                current_layer = Some(layer);
+            }
+
+            blend.apply();
+            first.texture.bind();
+            self.draw_instances(start, count);
+
+            start += count;
+        }
+
+        BlendMode::Alpha.apply();
+
+        unsafe {
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
+            gl_call!(gl::BindVertexArray(0));
+        }
+
+        self.queue.clear();
+    }
+
+    /// Streams the instances of the frame into the instance buffer, growing it when needed
+    fn upload_instances(&mut self) {
+        let stride = std::mem::size_of::<SpriteInstance>();
+
+        if self.instances.len() > self.instance_capacity {
+            self.instance_capacity = self.instances.len().next_power_of_two();
+        }
 
         unsafe {
-            gl::ActiveTexture(gl::TEXTURE0);
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_vbo.id()));
+            // Orphan the old storage so the driver doesn't have to wait for the previous frame
+            gl_call!(gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (stride * self.instance_capacity) as GLsizeiptr,
+                std::ptr::null(),
+                gl::STREAM_DRAW,
+            ));
+            gl_call!(gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (stride * self.instances.len()) as GLsizeiptr,
+                self.instances.as_ptr() as *const _,
+            ));
+            gl_call!(gl::BindBuffer(gl::ARRAY_BUFFER, 0));
         }
+    }
 
-        texture.bind();
+    /// Points the instance attributes at `first` (base instance isn't available in GL 3.3) and draws `count` quads
+    fn draw_instances(&self, first: usize, count: usize) {
+        let stride = std::mem::size_of::<SpriteInstance>();
+        let vec4_size = 4 * std::mem::size_of::<f32>();
+        let base = first * stride;
 
         unsafe {
-            gl::BindVertexArray(self.quad_vao);
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
-            gl::BindVertexArray(0);
+            for column in 0..4 {
+                gl_call!(gl::VertexAttribPointer(
+                    1 + column as GLuint,
+                    4,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as GLsizei,
+                    (base + column * vec4_size) as *const _,
+                ));
+            }
+
+            for (location, offset) in [(5, 4), (6, 5)] {
+                gl_call!(gl::VertexAttribPointer(
+                    location,
+                    4,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as GLsizei,
+                    (base + offset * vec4_size) as *const _,
+                ));
+            }
+
+            gl_call!(gl::DrawArraysInstanced(
+                gl::TRIANGLES,
+                0,
+                6,
+                count as GLsizei
+            ));
         }
     }
 }
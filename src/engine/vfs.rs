@@ -0,0 +1,375 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Cursor, Read, Seek},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use include_dir::{include_dir, Dir};
+
+use super::{archive::Archive, error::Error};
+
+/// Shaders the engine's renderers are written against, available under `shaders/`
+static ENGINE_SHADERS: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/engine/shaders");
+
+/// Something asset files are read from, its files appear below `mount_point`
+#[derive(Debug, Clone)]
+enum Mount {
+    Directory {
+        mount_point: String,
+        root: PathBuf,
+    },
+    Embedded {
+        mount_point: String,
+        dir: &'static Dir<'static>,
+    },
+    Archive {
+        mount_point: String,
+        archive: Arc<Archive>,
+    },
+}
+
+/// A file found in one of the mounts, it can be opened any number of times
+#[derive(Clone, PartialEq, Eq)]
+pub enum VfsFile {
+    Disk(PathBuf),
+    Embedded(&'static [u8]),
+    Archive { archive: Arc<Archive>, name: String },
+}
+
+/// A reader over a file which can be sent to another thread, e.g. to stream music
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// Read-only view of the asset files, merged from directories on disk, archives and files
+/// embedded into the binary. Paths are relative and use `/` whatever the platform, when several mounts
+/// have the same file the one mounted last wins, so mounts are added from lowest to highest
+/// priority.
+#[derive(Debug, Clone)]
+pub struct Vfs {
+    mounts: Vec<Mount>,
+}
+
+impl Vfs {
+    /// Has the engine's built-in shaders, in debug builds they're read from the source tree
+    /// instead so editing them works with hot-reloading
+    pub fn new() -> Self {
+        let mut vfs = Self { mounts: vec![] };
+        vfs.mount_embedded("shaders", &ENGINE_SHADERS);
+
+        if cfg!(debug_assertions) {
+            vfs.mount_dir(
+                "shaders",
+                concat!(env!("CARGO_MANIFEST_DIR"), "/src/engine/shaders"),
+            );
+        }
+
+        vfs
+    }
+
+    /// Mounts a directory on disk, a directory which doesn't exist is skipped
+    pub fn mount_dir(&mut self, mount_point: &str, root: impl Into<PathBuf>) {
+        let root = root.into();
+
+        if root.is_dir() {
+            self.mounts.push(Mount::Directory {
+                mount_point: mount_point.to_string(),
+                root,
+            });
+        }
+    }
+
+    /// Mounts files embedded with `include_dir!`
+    pub fn mount_embedded(&mut self, mount_point: &str, dir: &'static Dir<'static>) {
+        self.mounts.push(Mount::Embedded {
+            mount_point: mount_point.to_string(),
+            dir,
+        });
+    }
+
+    /// Mounts an archive built by `breakout-pack`, its index is checked right away
+    pub fn mount_archive(
+        &mut self,
+        mount_point: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<(), Error> {
+        self.mounts.push(Mount::Archive {
+            mount_point: mount_point.to_string(),
+            archive: Arc::new(Archive::open(path)?),
+        });
+
+        Ok(())
+    }
+
+    /// Looks a file up in the mounts, from the last mounted to the first
+    pub fn find(&self, path: &str) -> Result<VfsFile, Error> {
+        let normalized = normalize(path).ok_or_else(|| {
+            Error::io(
+                path,
+                io::Error::new(io::ErrorKind::InvalidInput, "path leaves the asset root"),
+            )
+        })?;
+
+        self.mounts
+            .iter()
+            .rev()
+            .find_map(|mount| mount.find(&normalized))
+            .ok_or_else(|| {
+                Error::io(
+                    path,
+                    io::Error::new(io::ErrorKind::NotFound, "not found in any asset directory"),
+                )
+            })
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.find(path)?.read(path)
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, Error> {
+        String::from_utf8(self.read(path)?).map_err(|e| Error::invalid_asset(path, e))
+    }
+
+    /// Last modification of the file, `None` for files that can't change such as embedded ones
+    pub fn modified(&self, path: &str) -> Option<SystemTime> {
+        self.find(path).ok()?.modified()
+    }
+}
+
+impl VfsFile {
+    /// `path` is what the file was found under, errors are reported with it
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            VfsFile::Disk(file) => std::fs::read(file).map_err(|e| Error::io(path, e)),
+            VfsFile::Embedded(contents) => Ok(contents.to_vec()),
+            VfsFile::Archive { archive, name } => Ok(archive.read(name)?),
+        }
+    }
+
+    pub fn open(&self, path: &str) -> Result<Box<dyn ReadSeek>, Error> {
+        match self {
+            VfsFile::Disk(file) => {
+                let file = File::open(file).map_err(|e| Error::io(path, e))?;
+                Ok(Box::new(BufReader::new(file)))
+            }
+            VfsFile::Embedded(contents) => Ok(Box::new(Cursor::new(*contents))),
+            // Deflate streams can't seek, so the whole file is decompressed up front
+            VfsFile::Archive { .. } => Ok(Box::new(Cursor::new(self.read(path)?))),
+        }
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        match self {
+            VfsFile::Disk(path) => path.metadata().and_then(|m| m.modified()).ok(),
+            VfsFile::Embedded(_) | VfsFile::Archive { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for VfsFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VfsFile::Disk(path) => f.debug_tuple("Disk").field(path).finish(),
+            VfsFile::Embedded(contents) => write!(f, "Embedded({} bytes)", contents.len()),
+            VfsFile::Archive { archive, name } => f
+                .debug_struct("Archive")
+                .field("archive", archive)
+                .field("name", name)
+                .finish(),
+        }
+    }
+}
+
+impl Mount {
+    fn find(&self, path: &str) -> Option<VfsFile> {
+        match self {
+            Mount::Directory { mount_point, root } => {
+                let file = root.join(strip_mount_point(mount_point, path)?);
+                file.is_file().then_some(VfsFile::Disk(file))
+            }
+            Mount::Embedded { mount_point, dir } => dir
+                .get_file(strip_mount_point(mount_point, path)?)
+                .map(|file| VfsFile::Embedded(file.contents())),
+            Mount::Archive {
+                mount_point,
+                archive,
+            } => {
+                let name = strip_mount_point(mount_point, path)?;
+
+                archive.contains(name).then(|| VfsFile::Archive {
+                    archive: archive.clone(),
+                    name: name.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Directories on disk searched for assets from lowest to highest priority: the `assets`
+/// directory next to the executable, then `<data dir>/<app_name>` for every XDG data directory
+pub fn search_paths(app_name: &str) -> Vec<PathBuf> {
+    let executable_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("assets")));
+
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+
+    // XDG_DATA_DIRS lists the most important directory first
+    let mut data_dirs: Vec<PathBuf> = data_dirs.split(':').rev().map(PathBuf::from).collect();
+    data_dirs.extend(data_home);
+
+    executable_dir
+        .into_iter()
+        .chain(data_dirs.into_iter().map(|dir| dir.join(app_name)))
+        .collect()
+}
+
+/// Resolves `relative` against the directory `file` is in
+pub fn join(file: &str, relative: &str) -> String {
+    match file.rsplit_once('/') {
+        Some((directory, _)) => format!("{}/{}", directory, relative),
+        None => relative.to_string(),
+    }
+}
+
+/// Removes `.` and `..` components and doubled separators, `None` if `..` goes above the root
+fn normalize(path: &str) -> Option<String> {
+    let mut components = vec![];
+
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop()?;
+            }
+            _ => components.push(component),
+        }
+    }
+
+    Some(components.join("/"))
+}
+
+fn strip_mount_point<'a>(mount_point: &str, path: &'a str) -> Option<&'a str> {
+    if mount_point.is_empty() {
+        return Some(path);
+    }
+
+    path.strip_prefix(mount_point)?.strip_prefix('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::engine::archive::{ArchiveWriter, Compression};
+
+    fn empty() -> Vfs {
+        Vfs { mounts: vec![] }
+    }
+
+    fn write_archive(path: &Path, files: &[(&str, &[u8])]) {
+        let mut writer = ArchiveWriter::new();
+
+        for (name, contents) in files {
+            writer.add(name, contents, Compression::Stored).unwrap();
+        }
+        writer.write(path).unwrap();
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize("a/b.png").as_deref(), Some("a/b.png"));
+        assert_eq!(normalize("./a//b/./c").as_deref(), Some("a/b/c"));
+        assert_eq!(normalize("a/../b").as_deref(), Some("b"));
+        assert_eq!(normalize("a/b/..").as_deref(), Some("a"));
+        assert_eq!(normalize("/a/").as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn paths_can_not_escape_the_root() {
+        assert_eq!(normalize(".."), None);
+        assert_eq!(normalize("a/../../b"), None);
+        assert_eq!(normalize("../a"), None);
+
+        let vfs = Vfs::new();
+        assert!(vfs.find("../shaders/sprite.vert").is_err());
+    }
+
+    #[test]
+    fn strips_mount_points() {
+        assert_eq!(strip_mount_point("", "a/b"), Some("a/b"));
+        assert_eq!(strip_mount_point("a", "a/b"), Some("b"));
+        assert_eq!(strip_mount_point("a/b", "a/b/c"), Some("c"));
+        // Only whole components match
+        assert_eq!(strip_mount_point("a", "ab/c"), None);
+        assert_eq!(strip_mount_point("a", "a"), None);
+        assert_eq!(strip_mount_point("a", "b/a/c"), None);
+    }
+
+    #[test]
+    fn joins_relative_to_the_directory_of_a_file() {
+        assert_eq!(join("fonts/hud.fnt", "hud_0.png"), "fonts/hud_0.png");
+        assert_eq!(join("a/b/c.json", "../d.png"), "a/b/../d.png");
+        assert_eq!(join("hud.fnt", "hud_0.png"), "hud_0.png");
+    }
+
+    #[test]
+    fn mounted_last_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let shaders = dir.path().join("shaders");
+        fs::create_dir(&shaders).unwrap();
+        fs::write(shaders.join("sprite.vert"), b"disk").unwrap();
+        let archive = dir.path().join("shaders.pak");
+        write_archive(&archive, &[("sprite.vert", b"archive")]);
+
+        let mut vfs = empty();
+        vfs.mount_embedded("shaders", &ENGINE_SHADERS);
+        assert!(matches!(
+            vfs.find("shaders/sprite.vert").unwrap(),
+            VfsFile::Embedded(_)
+        ));
+
+        vfs.mount_dir("shaders", &shaders);
+        assert_eq!(vfs.read("shaders/sprite.vert").unwrap(), b"disk");
+
+        vfs.mount_archive("shaders", &archive).unwrap();
+        assert_eq!(vfs.read("shaders/sprite.vert").unwrap(), b"archive");
+
+        vfs.mount_dir("shaders", &shaders);
+        assert_eq!(vfs.read("shaders/sprite.vert").unwrap(), b"disk");
+
+        // Files the later mounts don't have still come from the earlier ones
+        assert_eq!(
+            vfs.read("shaders/sprite.frag").unwrap(),
+            ENGINE_SHADERS.get_file("sprite.frag").unwrap().contents()
+        );
+        assert!(vfs.find("shaders/missing.vert").is_err());
+    }
+
+    #[test]
+    fn mounts_only_see_paths_below_their_mount_point() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), b"a").unwrap();
+
+        let mut vfs = empty();
+        vfs.mount_dir("data", dir.path());
+        // Missing directories are skipped rather than failing every lookup
+        vfs.mount_dir("", dir.path().join("missing"));
+
+        assert_eq!(vfs.mounts.len(), 1);
+        assert_eq!(vfs.read("data/a.txt").unwrap(), b"a");
+        assert_eq!(vfs.read("./data/../data/a.txt").unwrap(), b"a");
+        assert!(vfs.find("a.txt").is_err());
+    }
+}
@@ -0,0 +1,167 @@
+/// How the logical (virtual) resolution of the game is mapped onto the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalingPolicy {
+    /// Fills the whole window, the aspect ratio is not preserved
+    Stretch,
+    /// Scales as much as possible while keeping the aspect ratio, leftover space becomes bars
+    Letterbox,
+    /// Like letterbox but only scales by whole numbers so pixels stay crisp
+    IntegerScale,
+}
+
+/// Area of the drawable surface the logical resolution is rendered into.
+/// Coordinates are in physical pixels with the origin at the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    logical_width: u32,
+    logical_height: u32,
+    drawable_height: u32,
+}
+
+impl Viewport {
+    pub fn compute(
+        policy: ScalingPolicy,
+        logical_width: u32,
+        logical_height: u32,
+        drawable_width: u32,
+        drawable_height: u32,
+    ) -> Self {
+        let scale_x = drawable_width as f32 / logical_width as f32;
+        let scale_y = drawable_height as f32 / logical_height as f32;
+
+        let (width, height) = match policy {
+            ScalingPolicy::Stretch => (drawable_width, drawable_height),
+            ScalingPolicy::Letterbox => {
+                let scale = scale_x.min(scale_y);
+
+                (
+                    (logical_width as f32 * scale).round() as u32,
+                    (logical_height as f32 * scale).round() as u32,
+                )
+            }
+            ScalingPolicy::IntegerScale => {
+                // When the window is smaller than the logical resolution we can't go below 1x
+                let scale = (scale_x.min(scale_y).floor() as u32).max(1);
+
+                (logical_width * scale, logical_height * scale)
+            }
+        };
+
+        // A minimized window reports a zero sized drawable, keep the viewport valid regardless
+        let (width, height) = (width.max(1), height.max(1));
+
+        Self {
+            x: (drawable_width as i32 - width as i32) / 2,
+            y: (drawable_height as i32 - height as i32) / 2,
+            width,
+            height,
+            logical_width,
+            logical_height,
+            drawable_height,
+        }
+    }
+
+    /// Makes OpenGL render into this viewport, OpenGL's origin is the bottom left corner
+    pub fn apply(&self) {
+        let gl_y = self.drawable_height as i32 - self.y - self.height as i32;
+
+        unsafe {
+            gl_call!(gl::Viewport(
+                self.x,
+                gl_y,
+                self.width as i32,
+                self.height as i32
+            ));
+        }
+    }
+
+    /// Converts a position on the drawable surface into logical coordinates
+    pub fn drawable_to_logical(&self, position: glam::Vec2) -> glam::Vec2 {
+        glam::vec2(
+            (position.x - self.x as f32) * self.logical_width as f32 / self.width as f32,
+            (position.y - self.y as f32) * self.logical_height as f32 / self.height as f32,
+        )
+    }
+
+    /// Converts a distance on the drawable surface into a logical distance
+    pub fn drawable_delta_to_logical(&self, delta: glam::Vec2) -> glam::Vec2 {
+        glam::vec2(
+            delta.x * self.logical_width as f32 / self.width as f32,
+            delta.y * self.logical_height as f32 / self.height as f32,
+        )
+    }
+
+    pub fn logical_size(&self) -> (u32, u32) {
+        (self.logical_width, self.logical_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretch_fills_the_drawable() {
+        let viewport = Viewport::compute(ScalingPolicy::Stretch, 800, 600, 1000, 500);
+
+        assert_eq!((viewport.x, viewport.y), (0, 0));
+        assert_eq!((viewport.width, viewport.height), (1000, 500));
+    }
+
+    #[test]
+    fn letterbox_adds_bars_on_the_sides() {
+        let viewport = Viewport::compute(ScalingPolicy::Letterbox, 800, 600, 1920, 1080);
+
+        assert_eq!((viewport.width, viewport.height), (1440, 1080));
+        assert_eq!((viewport.x, viewport.y), (240, 0));
+    }
+
+    #[test]
+    fn letterbox_adds_bars_above_and_below() {
+        let viewport = Viewport::compute(ScalingPolicy::Letterbox, 800, 600, 800, 1000);
+
+        assert_eq!((viewport.width, viewport.height), (800, 600));
+        assert_eq!((viewport.x, viewport.y), (0, 200));
+    }
+
+    #[test]
+    fn integer_scale_only_uses_whole_factors() {
+        let viewport = Viewport::compute(ScalingPolicy::IntegerScale, 320, 240, 1000, 800);
+
+        assert_eq!((viewport.width, viewport.height), (960, 720));
+        assert_eq!((viewport.x, viewport.y), (20, 40));
+    }
+
+    #[test]
+    fn integer_scale_does_not_go_below_one() {
+        let viewport = Viewport::compute(ScalingPolicy::IntegerScale, 800, 600, 400, 300);
+
+        assert_eq!((viewport.width, viewport.height), (800, 600));
+        assert_eq!((viewport.x, viewport.y), (-200, -150));
+    }
+
+    #[test]
+    fn minimized_window_keeps_a_valid_size() {
+        let viewport = Viewport::compute(ScalingPolicy::Letterbox, 800, 600, 0, 0);
+
+        assert_eq!((viewport.width, viewport.height), (1, 1));
+    }
+
+    #[test]
+    fn converts_drawable_positions_to_logical() {
+        let viewport = Viewport::compute(ScalingPolicy::Letterbox, 800, 600, 1920, 1080);
+
+        let top_left = viewport.drawable_to_logical(glam::vec2(240.0, 0.0));
+        let center = viewport.drawable_to_logical(glam::vec2(960.0, 540.0));
+
+        assert!(top_left.abs_diff_eq(glam::Vec2::ZERO, 1e-4));
+        assert!(center.abs_diff_eq(glam::vec2(400.0, 300.0), 1e-4));
+        assert!(viewport
+            .drawable_delta_to_logical(glam::vec2(18.0, 9.0))
+            .abs_diff_eq(glam::vec2(10.0, 5.0), 1e-4));
+    }
+}
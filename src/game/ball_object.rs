@@ -1,6 +1,6 @@
-use crate::engine::{SpriteRenderer, Texture};
+use crate::engine::{Drawable, Layer, SpriteRenderer, TextureRegion};
 
-use super::{game_object::Drawable, Collision, Direction, Game, GameObject};
+use super::{Collision, Direction, GameObject};
 
 pub struct BallObject {
     game_object: GameObject,
@@ -9,6 +9,7 @@ pub struct BallObject {
 }
 
 impl BallObject {
+    #[allow(dead_code)]
     pub fn new() -> Self {
         Self {
             radius: 12.5,
@@ -17,7 +18,12 @@ impl BallObject {
         }
     }
 
-    pub fn build(pos: glam::Vec2, radius: f32, velocity: glam::Vec2, sprite: Texture) -> Self {
+    pub fn build(
+        pos: glam::Vec2,
+        radius: f32,
+        velocity: glam::Vec2,
+        sprite: TextureRegion,
+    ) -> Self {
         Self {
             radius: 12.5,
             stuck: true,
@@ -58,12 +64,8 @@ impl BallObject {
         }
     }
 
-    pub fn draw(&self, sprite_renderer: &SpriteRenderer) {
-        self.game_object.draw(sprite_renderer)
-    }
-
     pub fn reset(&mut self, position: glam::Vec2, velocity: glam::Vec2) {
-        *self.game_object.mut_position() = position;
+        self.game_object.set_position(position);
         *self.game_object.mut_velocity() = velocity;
         self.stuck = true;
     }
@@ -76,8 +78,8 @@ impl BallObject {
         self.stuck = value;
     }
 
-    pub fn collides(&self, rhs: &GameObject) -> Collision {
-        let center = *self.game_object.position() + self.radius;
+    pub fn collides(&self, rhs: &GameObject) -> Option<Collision> {
+        let center = self.center();
         let aabb_half_extents = *rhs.size() / 2.0;
         let aabb_center = *rhs.position() + aabb_half_extents;
         let difference = center - aabb_center;
@@ -85,11 +87,27 @@ impl BallObject {
         let closest = aabb_center + clamped;
         let difference = closest - center;
 
-        if difference.length() < self.radius {
-            (true, self.vector_direction(difference), difference)
-        } else {
-            (false, Direction::Up, glam::vec2(0.0, 0.0))
+        if difference.length() >= self.radius {
+            return None;
         }
+
+        let direction = self.vector_direction(difference);
+        let penetration = match direction {
+            Direction::Left => glam::vec2(self.radius - difference.x.abs(), 0.0),
+            Direction::Right => glam::vec2(difference.x.abs() - self.radius, 0.0),
+            Direction::Up => glam::vec2(0.0, difference.y.abs() - self.radius),
+            Direction::Down => glam::vec2(0.0, self.radius - difference.y.abs()),
+        };
+
+        Some(Collision {
+            direction,
+            closest,
+            penetration,
+        })
+    }
+
+    pub fn center(&self) -> glam::Vec2 {
+        *self.game_object.position() + self.radius
     }
 
     pub fn radius(&self) -> f32 {
@@ -105,7 +123,7 @@ impl BallObject {
     }
 
     fn vector_direction(&self, target: glam::Vec2) -> Direction {
-        let compass = vec![
+        let compass = [
             glam::vec2(0.0, 1.0),
             glam::vec2(1.0, 0.0),
             glam::vec2(0.0, -1.0),
@@ -135,3 +153,17 @@ impl BallObject {
         }
     }
 }
+
+impl Drawable for BallObject {
+    fn layer(&self) -> Layer {
+        self.game_object.layer()
+    }
+
+    fn sort_key(&self) -> i32 {
+        self.game_object.sort_key()
+    }
+
+    fn draw(&self, sprite_renderer: &mut SpriteRenderer, alpha: f32) {
+        self.game_object.draw(sprite_renderer, alpha)
+    }
+}
@@ -0,0 +1,229 @@
+use crate::engine::{Font, FrameStats, SpriteRenderer, TextStyle};
+
+use super::{BallObject, Collision, GameLevel, GameObject};
+
+/// Frames shown in the frame time graph
+const GRAPH_FRAMES: usize = 120;
+const GRAPH_BAR_WIDTH: f32 = 2.0;
+const GRAPH_HEIGHT: f32 = 80.0;
+/// Frame time at the top of the graph, longer frames are cut off
+const GRAPH_MAX_FRAME_TIME: f32 = 1.0 / 20.0;
+/// Frame time of 60 frames per second, drawn as a line across the graph
+const TARGET_FRAME_TIME: f32 = 1.0 / 60.0;
+const MARGIN: f32 = 10.0;
+const LINE_THICKNESS: f32 = 1.0;
+/// Seconds a contact stays visible, they only last a tick otherwise
+const CONTACT_LIFETIME: f32 = 0.75;
+/// Penetrations are a few pixels deep at most, they're scaled up to be readable
+const PENETRATION_SCALE: f32 = 4.0;
+const POINT_SIZE: f32 = 5.0;
+
+const BRICK_COLOR: glam::Vec4 = glam::vec4(0.2, 1.0, 0.2, 0.8);
+const SOLID_BRICK_COLOR: glam::Vec4 = glam::vec4(1.0, 0.6, 0.1, 0.8);
+const PLAYER_COLOR: glam::Vec4 = glam::vec4(0.2, 0.8, 1.0, 0.9);
+const BALL_COLOR: glam::Vec4 = glam::vec4(1.0, 1.0, 0.2, 0.9);
+const CLOSEST_POINT_COLOR: glam::Vec4 = glam::vec4(1.0, 0.2, 1.0, 1.0);
+const PENETRATION_COLOR: glam::Vec4 = glam::vec4(1.0, 0.2, 0.2, 1.0);
+const GRAPH_BACKGROUND_COLOR: glam::Vec4 = glam::vec4(0.0, 0.0, 0.0, 0.6);
+const FAST_FRAME_COLOR: glam::Vec4 = glam::vec4(0.2, 1.0, 0.2, 0.9);
+const SLOW_FRAME_COLOR: glam::Vec4 = glam::vec4(1.0, 0.2, 0.2, 0.9);
+
+/// A collision of the ball remembered for drawing
+struct Contact {
+    /// Center of the ball when the collision was detected, before it was resolved
+    center: glam::Vec2,
+    radius: f32,
+    collision: Collision,
+    /// Seconds since the collision
+    age: f32,
+}
+
+/// Shows frame times and what the collision code sees, toggled while the game runs
+pub struct DebugOverlay {
+    visible: bool,
+    frame_stats: FrameStats,
+    contacts: Vec<Contact>,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            frame_stats: FrameStats::new(GRAPH_FRAMES),
+            contacts: vec![],
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.contacts.clear();
+    }
+
+    /// Has to be called once per rendered frame, even while hidden, to keep the graph current
+    pub fn tick_frame(&mut self) {
+        self.frame_stats.tick();
+    }
+
+    /// Remembers a collision of `ball`, should be called before the collision is resolved
+    pub fn record_contact(&mut self, ball: &BallObject, collision: &Collision) {
+        if !self.visible {
+            return;
+        }
+
+        self.contacts.push(Contact {
+            center: ball.center(),
+            radius: ball.radius(),
+            collision: *collision,
+            age: 0.0,
+        });
+    }
+
+    /// Ages the contacts by `delta_time` seconds, forgetting the old ones
+    pub fn update(&mut self, delta_time: f32) {
+        for contact in self.contacts.iter_mut() {
+            contact.age += delta_time;
+        }
+
+        self.contacts
+            .retain(|contact| contact.age < CONTACT_LIFETIME);
+    }
+
+    /// Queues the collision shapes, in world coordinates
+    pub fn draw_world(
+        &self,
+        sprite_renderer: &mut SpriteRenderer,
+        level: &GameLevel,
+        player: &GameObject,
+        ball: &BallObject,
+        alpha: f32,
+    ) {
+        for brick in level.bricks().iter().filter(|brick| !brick.destroyed()) {
+            let color = if *brick.is_solid() {
+                SOLID_BRICK_COLOR
+            } else {
+                BRICK_COLOR
+            };
+
+            sprite_renderer.draw_rect_outline(
+                *brick.position(),
+                *brick.size(),
+                LINE_THICKNESS,
+                color,
+            );
+        }
+
+        sprite_renderer.draw_rect_outline(
+            player.interpolated_position(alpha),
+            *player.size(),
+            LINE_THICKNESS,
+            PLAYER_COLOR,
+        );
+
+        let ball_center = ball.game_object().interpolated_position(alpha) + ball.radius();
+        sprite_renderer.draw_circle_outline(ball_center, ball.radius(), LINE_THICKNESS, BALL_COLOR);
+
+        for contact in self.contacts.iter() {
+            let fade = 1.0 - contact.age / CONTACT_LIFETIME;
+            let faded = |color: glam::Vec4| color * glam::vec4(1.0, 1.0, 1.0, fade);
+            let collision = &contact.collision;
+
+            sprite_renderer.draw_circle_outline(
+                contact.center,
+                contact.radius,
+                LINE_THICKNESS,
+                faded(BALL_COLOR),
+            );
+            sprite_renderer.draw_line(
+                contact.center,
+                collision.closest,
+                LINE_THICKNESS,
+                faded(CLOSEST_POINT_COLOR),
+            );
+            sprite_renderer.draw_rect(
+                collision.closest - POINT_SIZE / 2.0,
+                glam::Vec2::splat(POINT_SIZE),
+                faded(CLOSEST_POINT_COLOR),
+            );
+            sprite_renderer.draw_line(
+                collision.closest,
+                collision.closest + collision.penetration * PENETRATION_SCALE,
+                LINE_THICKNESS * 2.0,
+                faded(PENETRATION_COLOR),
+            );
+        }
+    }
+
+    /// Queues the frame rate, frame time graph and the sides the ball last hit,
+    /// in screen coordinates
+    pub fn draw_stats(
+        &self,
+        sprite_renderer: &mut SpriteRenderer,
+        font: &Font,
+        screen_height: u32,
+    ) {
+        let graph_size = glam::vec2(GRAPH_FRAMES as f32 * GRAPH_BAR_WIDTH, GRAPH_HEIGHT);
+        let graph_position = glam::vec2(MARGIN, screen_height as f32 - MARGIN - graph_size.y);
+
+        sprite_renderer.draw_rect(graph_position, graph_size, GRAPH_BACKGROUND_COLOR);
+
+        // Newest frame on the right, like a scrolling oscilloscope
+        let skipped = GRAPH_FRAMES - self.frame_stats.frame_times().count().min(GRAPH_FRAMES);
+
+        for (i, frame_time) in self.frame_stats.frame_times().enumerate() {
+            let height = (frame_time / GRAPH_MAX_FRAME_TIME).min(1.0) * graph_size.y;
+            let color = if frame_time > TARGET_FRAME_TIME * 1.05 {
+                SLOW_FRAME_COLOR
+            } else {
+                FAST_FRAME_COLOR
+            };
+
+            sprite_renderer.draw_rect(
+                graph_position
+                    + glam::vec2(
+                        (skipped + i) as f32 * GRAPH_BAR_WIDTH,
+                        graph_size.y - height,
+                    ),
+                glam::vec2(GRAPH_BAR_WIDTH, height),
+                color,
+            );
+        }
+
+        let target_y =
+            graph_position.y + graph_size.y * (1.0 - TARGET_FRAME_TIME / GRAPH_MAX_FRAME_TIME);
+        sprite_renderer.draw_line(
+            glam::vec2(graph_position.x, target_y),
+            glam::vec2(graph_position.x + graph_size.x, target_y),
+            LINE_THICKNESS,
+            glam::vec4(1.0, 1.0, 1.0, 0.5),
+        );
+
+        let mut text = format!(
+            "FPS: {:.0}\nFrame: {:.2} ms (max {:.2} ms)",
+            self.frame_stats.fps(),
+            self.frame_stats.average() * 1000.0,
+            self.frame_stats.max() * 1000.0,
+        );
+
+        if let Some(contact) = self.contacts.last() {
+            text.push_str(&format!(
+                "\nHit: {:?} (penetration {:.2}, {:.2})",
+                contact.collision.direction,
+                contact.collision.penetration.x,
+                contact.collision.penetration.y,
+            ));
+        }
+
+        let lines = text.lines().count() as f32;
+
+        sprite_renderer.draw_text(
+            font,
+            &text,
+            graph_position - glam::vec2(0.0, MARGIN + lines * font.line_height()),
+            &TextStyle::default(),
+        );
+    }
+}
@@ -1,44 +1,89 @@
-use std::fs;
+use crate::engine::{Drawable, Error, ResourceManager, SpriteRenderer};
 
-use crate::engine::{ResourceManager, SpriteRenderer};
-
-use super::{game_object::Drawable, GameObject};
+use super::GameObject;
 
 #[derive(Debug)]
 pub struct GameLevel {
     bricks: Vec<GameObject>,
 }
 
-impl GameLevel {
-    pub fn build(
-        level_file: &str,
-        level_width: u32,
-        level_height: u32,
-        resource_manager: &ResourceManager,
-    ) -> Result<Self, String> {
-        let buffer = match fs::read_to_string(level_file) {
-            Ok(b) => b,
-            Err(_) => return Err(String::from("Couldn't open/find provided level file.")),
+/// The bricks of a level file, one row per line. Parsing doesn't need any GL state, so it can
+/// happen on a loader thread.
+#[derive(Debug, Clone)]
+pub struct LevelData {
+    tile_data: Vec<Vec<u8>>,
+}
+
+impl LevelData {
+    /// Parses rows of brick numbers separated by whitespace, every row needs as many bricks
+    /// as the first one. `path` is only used to report errors.
+    pub fn parse(path: &str, file: &[u8]) -> Result<Self, Error> {
+        let buffer = std::str::from_utf8(file).map_err(|e| Error::invalid_asset(path, e))?;
+        let error = |line: usize, column: usize, message: String| Error::LevelParse {
+            path: path.to_string(),
+            line: line + 1,
+            column,
+            message,
         };
 
         let mut tile_data: Vec<Vec<u8>> = vec![];
 
-        for line in buffer.lines() {
-            let brick_line: Vec<u8> = line
-                .trim()
-                .split_ascii_whitespace()
-                .map(|brick| brick.trim().parse::<u8>().unwrap())
-                .collect();
+        for (index, line) in buffer.lines().enumerate() {
+            let mut brick_line = vec![];
+
+            for brick in line.split_whitespace() {
+                // `brick` is a slice of `line`, so the distance between them is its byte offset
+                let offset = brick.as_ptr() as usize - line.as_ptr() as usize;
+                let column = line[..offset].chars().count() + 1;
+                let number = brick.parse::<u8>().map_err(|e| {
+                    error(
+                        index,
+                        column,
+                        format!("'{}' isn't a brick number: {}", brick, e),
+                    )
+                })?;
+
+                brick_line.push(number);
+            }
+
+            if brick_line.is_empty() {
+                continue;
+            }
+
+            if let Some(first_line) = tile_data.first() {
+                if brick_line.len() != first_line.len() {
+                    return Err(error(
+                        index,
+                        1,
+                        format!(
+                            "row has {} bricks but the first row has {}",
+                            brick_line.len(),
+                            first_line.len()
+                        ),
+                    ));
+                }
+            }
 
             tile_data.push(brick_line);
         }
 
-        // calculate dimensions if we have any tile data available
-
-        if tile_data.len() == 0 {
-            return Err(String::from("No tile data in level file."));
+        if tile_data.is_empty() {
+            return Err(error(0, 1, String::from("No tile data in level file.")));
         }
 
+        Ok(Self { tile_data })
+    }
+}
+
+impl GameLevel {
+    pub fn build(
+        level: &LevelData,
+        level_width: u32,
+        level_height: u32,
+        resource_manager: &ResourceManager,
+    ) -> Self {
+        let tile_data = &level.tile_data;
+
         let mut bricks = vec![];
 
         let height = tile_data.len();
@@ -46,30 +91,30 @@ impl GameLevel {
         let unit_width = level_width as f32 / width as f32;
         let unit_height = level_height as f32 / height as f32;
 
-        for y in 0..height {
-            for x in 0..width {
-                if tile_data[y][x] == 1 {
+        for (y, row) in tile_data.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if tile == 1 {
                     let pos = glam::vec2(unit_width * x as f32, unit_height * y as f32);
                     let size = glam::vec2(unit_width, unit_height);
                     let mut obj = GameObject::build(
                         pos,
                         size,
-                        resource_manager.get_texture("block_solid").unwrap(),
+                        resource_manager.get_region("block_solid").unwrap(),
                         Some(glam::vec3(0.8, 0.8, 0.7)),
                         None,
                     );
                     obj.set_is_solid(true);
                     bricks.push(obj);
-                } else if tile_data[y][x] > 1 {
+                } else if tile > 1 {
                     let mut color = glam::vec3(1.0, 1.0, 1.0);
 
-                    if tile_data[y][x] == 2 {
+                    if tile == 2 {
                         color = glam::vec3(0.2, 0.6, 1.0);
-                    } else if tile_data[y][x] == 3 {
+                    } else if tile == 3 {
                         color = glam::vec3(0.0, 0.7, 0.0);
-                    } else if tile_data[y][x] == 4 {
+                    } else if tile == 4 {
                         color = glam::vec3(0.8, 0.8, 0.4);
-                    } else if tile_data[y][x] == 5 {
+                    } else if tile == 5 {
                         color = glam::vec3(1.0, 0.8, 0.0);
                     }
 
@@ -79,7 +124,7 @@ impl GameLevel {
                     bricks.push(GameObject::build(
                         pos,
                         size,
-                        resource_manager.get_texture("block").unwrap(),
+                        resource_manager.get_region("block").unwrap(),
                         Some(color),
                         None,
                     ));
@@ -87,15 +132,7 @@ impl GameLevel {
             }
         }
 
-        Ok(Self { bricks })
-    }
-
-    pub fn draw(&self, sprite_renderer: &SpriteRenderer) {
-        for tile in self.bricks.iter() {
-            if !tile.destroyed() {
-                tile.draw(sprite_renderer);
-            }
-        }
+        Self { bricks }
     }
 
     pub fn is_completed(&self) -> bool {
@@ -104,7 +141,89 @@ impl GameLevel {
             .all(|brick| *brick.is_solid() || *brick.destroyed())
     }
 
+    pub fn bricks(&self) -> &[GameObject] {
+        &self.bricks
+    }
+
     pub fn mut_bricks(&mut self) -> &mut Vec<GameObject> {
         &mut self.bricks
     }
 }
+
+impl Drawable for GameLevel {
+    fn draw(&self, sprite_renderer: &mut SpriteRenderer, alpha: f32) {
+        for tile in self.bricks.iter() {
+            if !tile.destroyed() {
+                sprite_renderer.submit(tile, alpha);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(file: &str) -> (usize, usize, String) {
+        match LevelData::parse("test.level", file.as_bytes()) {
+            Err(Error::LevelParse {
+                path,
+                line,
+                column,
+                message,
+            }) => {
+                assert_eq!(path, "test.level");
+                (line, column, message)
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_rows_of_bricks() {
+        let level = LevelData::parse("test.level", b"1 1 0\n\n2\t5 1\r\n").unwrap();
+
+        assert_eq!(level.tile_data, [[1, 1, 0], [2, 5, 1]]);
+    }
+
+    #[test]
+    fn reports_where_a_brick_is_wrong() {
+        let (line, column, message) = parse_error("1 1 1\n1  x 1\n");
+
+        assert_eq!((line, column), (2, 4));
+        assert!(
+            message.starts_with("'x' isn't a brick number"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        // Ideographic spaces are three bytes each in UTF-8
+        let (line, column, _) = parse_error("1\u{3000}\u{3000}999");
+
+        assert_eq!((line, column), (1, 4));
+    }
+
+    #[test]
+    fn rows_need_the_width_of_the_first() {
+        let (line, column, message) = parse_error("1 1 1\n\n1 1 1\n1 1\n");
+
+        assert_eq!((line, column), (4, 1));
+        assert_eq!(message, "row has 2 bricks but the first row has 3");
+    }
+
+    #[test]
+    fn empty_levels_are_rejected() {
+        assert_eq!(parse_error(" \n\n").0, 1);
+    }
+
+    #[test]
+    fn rejects_files_which_are_not_text() {
+        assert!(matches!(
+            LevelData::parse("test.level", &[0xff, 0xfe]),
+            Err(Error::InvalidAsset { .. })
+        ));
+    }
+}
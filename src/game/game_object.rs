@@ -1,30 +1,36 @@
-use crate::engine::{SpriteRenderer, Texture};
-
-pub trait Drawable {
-    fn draw(&self, sprite_renderer: &SpriteRenderer);
-}
+use crate::engine::{AnimationPlayer, Drawable, Layer, SpriteRenderer, TextureRegion};
 
 #[derive(Debug)]
 pub struct GameObject {
     position: glam::Vec2,
+    previous_position: glam::Vec2,
     size: glam::Vec2,
     velocity: glam::Vec2,
     color: glam::Vec3,
     rotation: f32,
     is_solid: bool,
     destroyed: bool,
-    sprite: Option<Texture>,
+    sprite: Option<TextureRegion>,
+    /// Overrides the sprite with the current frame while set
+    animation: Option<AnimationPlayer>,
+    layer: Layer,
+    sort_key: i32,
 }
 
 impl GameObject {
+    #[allow(dead_code)]
     pub fn new() -> Self {
         Self {
             position: glam::vec2(0.0, 0.0),
+            previous_position: glam::vec2(0.0, 0.0),
             size: glam::vec2(1.0, 1.0),
             velocity: glam::vec2(0.0, 0.0),
             color: glam::vec3(1.0, 1.0, 1.0),
             rotation: 0.0,
             sprite: None,
+            animation: None,
+            layer: Layer::World,
+            sort_key: 0,
             is_solid: false,
             destroyed: false,
         }
@@ -33,15 +39,19 @@ impl GameObject {
     pub fn build(
         pos: glam::Vec2,
         size: glam::Vec2,
-        sprite: Texture,
+        sprite: TextureRegion,
         color: Option<glam::Vec3>,
         velocity: Option<glam::Vec2>,
     ) -> Self {
         Self {
             position: pos,
+            previous_position: pos,
             size,
             rotation: 0.0,
             sprite: Some(sprite),
+            animation: None,
+            layer: Layer::World,
+            sort_key: 0,
             is_solid: false,
             destroyed: false,
             color: color.unwrap_or_else(|| glam::vec3(1.0, 1.0, 1.0)),
@@ -57,10 +67,30 @@ impl GameObject {
         &self.position
     }
 
+    /// Remembers the current position as the one of the previous tick, should be called
+    /// once at the start of every simulation tick before the object is moved
+    pub fn snapshot_position(&mut self) {
+        self.previous_position = self.position;
+    }
+
+    /// Moves the object without interpolating from where it was, used for teleports like resets
+    pub fn set_position(&mut self, position: glam::Vec2) {
+        self.position = position;
+        self.previous_position = position;
+    }
+
+    pub fn interpolated_position(&self, alpha: f32) -> glam::Vec2 {
+        self.previous_position.lerp(self.position, alpha)
+    }
+
     pub fn size(&self) -> &glam::Vec2 {
         &self.size
     }
 
+    pub fn color(&self) -> glam::Vec3 {
+        self.color
+    }
+
     pub fn mut_velocity(&mut self) -> &mut glam::Vec2 {
         &mut self.velocity
     }
@@ -85,6 +115,32 @@ impl GameObject {
         &self.destroyed
     }
 
+    /// Layer and sort key the object is submitted with
+    pub fn set_draw_order(&mut self, layer: Layer, sort_key: i32) {
+        self.layer = layer;
+        self.sort_key = sort_key;
+    }
+
+    pub fn set_animation(&mut self, animation: Option<AnimationPlayer>) {
+        self.animation = animation;
+    }
+
+    pub fn animation(&self) -> Option<&AnimationPlayer> {
+        self.animation.as_ref()
+    }
+
+    pub fn mut_animation(&mut self) -> Option<&mut AnimationPlayer> {
+        self.animation.as_mut()
+    }
+
+    /// Advances the animation, if any, by `delta_time` seconds
+    pub fn update_animation(&mut self, delta_time: f32) {
+        if let Some(animation) = &mut self.animation {
+            animation.update(delta_time);
+        }
+    }
+
+    #[allow(dead_code)]
     pub fn collides(&self, rhs: &GameObject) -> bool {
         let collision_x = self.position.x + self.size.x >= rhs.position.x
             && rhs.position.x + rhs.size.x >= self.position.x;
@@ -97,9 +153,29 @@ impl GameObject {
 }
 
 impl Drawable for GameObject {
-    fn draw(&self, sprite_renderer: &SpriteRenderer) {
-        if let Some(sprite) = self.sprite {
-            sprite_renderer.draw_sprite(sprite, self.position, self.size, self.rotation, self.color)
+    fn layer(&self) -> Layer {
+        self.layer
+    }
+
+    fn sort_key(&self) -> i32 {
+        self.sort_key
+    }
+
+    fn draw(&self, sprite_renderer: &mut SpriteRenderer, alpha: f32) {
+        let sprite = self
+            .animation
+            .as_ref()
+            .and_then(|animation| animation.region())
+            .or(self.sprite.as_ref());
+
+        if let Some(sprite) = sprite {
+            sprite_renderer.draw_region(
+                sprite,
+                self.interpolated_position(alpha),
+                self.size,
+                self.rotation,
+                self.color,
+            )
         }
     }
 }
@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use sdl2::EventPump;
+
+use crate::engine::{
+    Align, Error, Event, GameLoop, LoadProgress, LoopControl, Renderer, ResourceManager, Scancode,
+    SpriteRenderer, TextStyle, Vfs,
+};
+
+use super::{game_level::LevelData, ASSET_MANIFEST, LEVEL_PACK};
+
+/// Time spent every frame finishing loaded assets, the rest is left for drawing the screen
+const LOAD_BUDGET: Duration = Duration::from_millis(8);
+const BAR_SIZE: glam::Vec2 = glam::vec2(400.0, 24.0);
+const BAR_OUTLINE: f32 = 2.0;
+const BAR_COLOR: glam::Vec4 = glam::vec4(1.0, 1.0, 1.0, 1.0);
+const BACKGROUND_COLOR: glam::Vec4 = glam::vec4(0.0, 0.0, 0.0, 1.0);
+const MARGIN: f32 = 10.0;
+
+/// Shown while assets are decoded on loader threads, ends once everything is loaded
+pub struct LoadingScreen {
+    width: u32,
+    height: u32,
+    resource_manager: ResourceManager,
+    sprite_renderer: SpriteRenderer,
+    progress: LoadProgress,
+    /// Set once loading finished or failed
+    result: Option<Result<(), Error>>,
+    cancelled: bool,
+}
+
+impl LoadingScreen {
+    /// Starts loading the asset manifest and the levels of the level pack in the background
+    pub fn build(width: u32, height: u32, vfs: Vfs) -> Result<Self, Error> {
+        let mut resource_manager = ResourceManager::new(vfs);
+        resource_manager.set_hot_reload(cfg!(debug_assertions));
+
+        resource_manager.load_manifest_in_background(ASSET_MANIFEST)?;
+
+        let level_paths = resource_manager
+            .get_level_pack(LEVEL_PACK)
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "No level pack '{}' in {}",
+                    LEVEL_PACK, ASSET_MANIFEST
+                ))
+            })?
+            .to_vec();
+
+        for path in &level_paths {
+            resource_manager.load_data_in_background(path, LevelData::parse);
+        }
+
+        let sprite_renderer = SpriteRenderer::new(
+            width,
+            height,
+            resource_manager.get_shader("sprite").unwrap(),
+        );
+
+        Ok(Self {
+            width,
+            height,
+            resource_manager,
+            sprite_renderer,
+            progress: LoadProgress::default(),
+            result: None,
+            cancelled: false,
+        })
+    }
+
+    /// Shows the screen until loading is done, then hands over the loaded assets. `None` if the
+    /// window was closed before that.
+    pub fn run(mut self, renderer: &mut Renderer) -> Result<Option<ResourceManager>, Error> {
+        renderer.game_loop(&mut self);
+
+        match self.result {
+            _ if self.cancelled => Ok(None),
+            Some(Err(e)) => Err(e),
+            _ => Ok(Some(self.resource_manager)),
+        }
+    }
+
+    fn draw_progress(&mut self) {
+        let position = glam::vec2(
+            (self.width as f32 - BAR_SIZE.x) / 2.0,
+            (self.height as f32 - BAR_SIZE.y) / 2.0,
+        );
+        let filled = glam::vec2(BAR_SIZE.x * self.progress.fraction(), BAR_SIZE.y);
+
+        self.sprite_renderer.draw_rect(
+            glam::Vec2::ZERO,
+            glam::vec2(self.width as f32, self.height as f32),
+            BACKGROUND_COLOR,
+        );
+        self.sprite_renderer.draw_rect(position, filled, BAR_COLOR);
+        self.sprite_renderer
+            .draw_rect_outline(position, BAR_SIZE, BAR_OUTLINE, BAR_COLOR);
+
+        // Fonts are loaded in the background too, the bar is drawn on its own until one is ready
+        if let Some(hud) = self.resource_manager.get_font("hud") {
+            self.sprite_renderer.draw_text(
+                hud,
+                &format!("Loading {}/{}", self.progress.loaded, self.progress.total),
+                glam::vec2(
+                    self.width as f32 / 2.0,
+                    position.y - hud.line_height() - MARGIN,
+                ),
+                &TextStyle {
+                    align: Align::Center,
+                    color: BAR_COLOR,
+                    ..Default::default()
+                },
+            );
+        }
+
+        self.sprite_renderer.flush();
+    }
+}
+
+impl GameLoop for LoadingScreen {
+    fn process_events(&mut self, events: &[Event]) -> LoopControl {
+        self.cancelled |= events
+            .iter()
+            .any(|event| *event == Event::Quit || event.is_key_pressed(Scancode::Escape));
+
+        if self.cancelled || self.result.is_some() {
+            LoopControl::Quit
+        } else {
+            LoopControl::Continue
+        }
+    }
+
+    fn update(&mut self, _event_pump: &EventPump, _delta_time: f32) {}
+
+    fn draw(&mut self, _alpha: f32) {
+        // Uploads need the GL context, which is only current on this thread
+        match self.resource_manager.process_loaded(LOAD_BUDGET) {
+            Ok(progress) if progress.is_done() => {
+                self.progress = progress;
+                self.result = Some(Ok(()));
+            }
+            Ok(progress) => self.progress = progress,
+            Err(e) => self.result = Some(Err(e)),
+        }
+
+        self.draw_progress();
+    }
+}
@@ -1,18 +1,32 @@
-use sdl2::{keyboard::Scancode, EventPump};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-use crate::engine::{Renderer, ResourceManager, SpriteRenderer};
+use include_dir::{include_dir, Dir};
+use sdl2::EventPump;
+
+use crate::engine::{
+    archive, vfs, Align, AnimationClip, AnimationPlayer, Audio, BlendMode, Camera2D, Effect,
+    EmitterConfig, Error, Event, GameLoop, Layer, LoopControl, ParticleEmitter, PlaybackMode,
+    PostProcessor, Renderer, ResourceManager, Scancode, SoundParams, SpriteRenderer, TextStyle,
+    Vfs,
+};
 
 mod ball_object;
+mod debug_overlay;
 mod game_level;
 mod game_object;
+mod loading_screen;
 
 pub use ball_object::BallObject;
 pub use game_level::GameLevel;
 pub use game_object::GameObject;
+pub use loading_screen::LoadingScreen;
 
-use self::game_object::Drawable;
+use self::{debug_overlay::DebugOverlay, game_level::LevelData};
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     Up,
     Right,
@@ -20,9 +34,19 @@ pub enum Direction {
     Left,
 }
 
-type Collision = (bool, Direction, glam::Vec2);
+/// Contact between the ball and a box, as found by `BallObject::collides`
+#[derive(Debug, Clone, Copy)]
+pub struct Collision {
+    /// Side of the box the ball hit, as seen from the ball
+    pub direction: Direction,
+    /// Point of the box closest to the center of the ball
+    pub closest: glam::Vec2,
+    /// Moves the ball out of the box along the axis of `direction`
+    pub penetration: glam::Vec2,
+}
 
 #[derive(PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 enum GameState {
     PLAYING,
     MENU,
@@ -31,116 +55,301 @@ enum GameState {
 
 pub struct Game {
     state: GameState,
+    paused: bool,
     width: u32,
     height: u32,
     resource_manager: ResourceManager,
+    audio: Audio,
     sprite_renderer: SpriteRenderer,
+    post_processor: PostProcessor,
+    camera: Camera2D,
+    /// Seconds of simulated time, drives the animated post-processing effects
+    time: f32,
     levels: Vec<GameLevel>,
+    /// Every level as loaded, to rebuild it when it's restarted
+    level_data: Vec<LevelData>,
+    /// Bricks playing their break animation, removed once it's over
+    breaking_bricks: Vec<GameObject>,
+    brick_break: AnimationClip,
     level: usize,
+    lives: u32,
+    score: u32,
     player: GameObject,
     ball: BallObject,
+    ball_trail: ParticleEmitter,
+    debris: ParticleEmitter,
+    debug_overlay: DebugOverlay,
 }
 
+/// Assets built into the binary, so it runs without anything next to it
+static ASSETS: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/game/assets");
+const ASSET_MANIFEST: &str = "assets.toml";
+/// Name of the game's directory in the XDG data directories
+const DATA_DIR_NAME: &str = "breakout";
+const LEVEL_PACK: &str = "main";
 const PLAYER_SIZE: glam::Vec2 = glam::vec2(100.0, 20.0);
-const PLAYER_VELOCITY: f32 = 0.75;
+// velocities are in pixels per second
+const PLAYER_VELOCITY: f32 = 750.0;
 const PADDING: f32 = 10.0;
-const INITIAL_BALL_VELOCITY: glam::Vec2 = glam::vec2(0.15, -0.45);
+const INITIAL_BALL_VELOCITY: glam::Vec2 = glam::vec2(150.0, -450.0);
 const BALL_RADIUS: f32 = 12.5;
+const MSAA_SAMPLES: u32 = 4;
+const SOLID_TRAUMA: f32 = 0.25;
+const BALL_LOST_TRAUMA: f32 = 0.6;
+// sort keys within Layer::World, bricks use the default of 0
+const BREAKING_BRICK_ORDER: i32 = 1;
+const PLAYER_ORDER: i32 = 2;
+const BALL_TRAIL_ORDER: i32 = 3;
+const BALL_ORDER: i32 = 4;
+const DEBRIS_PER_BRICK: usize = 12;
+const BRICK_BREAK_FRAME_DURATION: f32 = 0.06;
+const PADDLE_IDLE_FRAMES: usize = 4;
+const PADDLE_IDLE_FRAME_DURATION: f32 = 0.15;
+const PADDLE_HIT_FRAME_DURATION: f32 = 0.05;
+const INITIAL_LIVES: u32 = 3;
+const BRICK_SCORE: u32 = 10;
+/// Seconds the music of two levels overlaps when switching between them
+const MUSIC_CROSSFADE: f32 = 1.5;
+const PADDLE_SOUND: SoundParams = SoundParams {
+    volume: 0.6,
+    volume_variation: 0.1,
+    pitch: 1.0,
+    pitch_variation: 0.05,
+};
+const BRICK_SOUND: SoundParams = SoundParams {
+    volume: 0.5,
+    volume_variation: 0.1,
+    pitch: 1.0,
+    pitch_variation: 0.15,
+};
+const SOLID_SOUND: SoundParams = SoundParams {
+    volume: 0.8,
+    volume_variation: 0.1,
+    pitch: 1.0,
+    pitch_variation: 0.1,
+};
+const LOST_SOUND: SoundParams = SoundParams {
+    volume: 0.7,
+    volume_variation: 0.0,
+    pitch: 1.0,
+    pitch_variation: 0.0,
+};
+
+/// Where the game's assets are read from, from lowest to highest priority: the assets built into
+/// the binary, in debug builds the source tree so edits show up without rebuilding, the standard
+/// search paths and finally `assets`, a directory or an archive usually given on the command line
+pub fn asset_vfs(assets: Option<PathBuf>) -> Vfs {
+    let mut vfs = Vfs::new();
+    vfs.mount_embedded("", &ASSETS);
+
+    if cfg!(debug_assertions) {
+        vfs.mount_dir("", concat!(env!("CARGO_MANIFEST_DIR"), "/src/game/assets"));
+    }
+
+    for dir in vfs::search_paths(DATA_DIR_NAME) {
+        mount_search_dir(&mut vfs, &dir);
+    }
+
+    match assets {
+        Some(path) if path.is_dir() => mount_search_dir(&mut vfs, &path),
+        Some(path) => mount_archive(&mut vfs, &path),
+        None => (),
+    }
+
+    vfs
+}
+
+/// Mounts the archives in `dir` in name order, then its loose files which take precedence
+fn mount_search_dir(vfs: &mut Vfs, dir: &Path) {
+    let mut archives: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == archive::EXTENSION))
+        .collect();
+    archives.sort();
+
+    for archive in archives {
+        mount_archive(vfs, &archive);
+    }
+
+    vfs.mount_dir("", dir);
+}
+
+/// A broken archive is skipped, the game can still run from the other assets
+fn mount_archive(vfs: &mut Vfs, path: &Path) {
+    if let Err(e) = vfs.mount_archive("", path) {
+        eprintln!("Skipping asset archive {}", e);
+    }
+}
 
 impl Game {
-    pub fn build(width: u32, height: u32) -> Result<Self, String> {
-        let mut resource_manager = ResourceManager::new();
-
-        // load textures
-        resource_manager.load_texture("./src/game/textures/background.jpeg", false, "background");
-        resource_manager.load_texture("./src/game/textures/awesomeface.png", true, "face");
-        resource_manager.load_texture("./src/game/textures/block.png", false, "block");
-        resource_manager.load_texture("./src/game/textures/block_solid.png", false, "block_solid");
-        resource_manager.load_texture("./src/game/textures/paddle.png", true, "paddle");
-
-        // load levels
-        let one = GameLevel::build(
-            "./src/game/levels/1.level",
-            width,
-            height / 2,
-            &resource_manager,
-        )
-        .unwrap();
-        let two = GameLevel::build(
-            "./src/game/levels/2.level",
-            width,
-            height / 2,
-            &resource_manager,
-        )
-        .unwrap();
-        let three = GameLevel::build(
-            "./src/game/levels/3.level",
-            width,
-            height / 2,
-            &resource_manager,
-        )
-        .unwrap();
-        let four = GameLevel::build(
-            "./src/game/levels/4.level",
-            width,
-            height / 2,
-            &resource_manager,
-        )
-        .unwrap();
-        let five = GameLevel::build(
-            "./src/game/levels/5.level",
-            width,
-            height / 2,
-            &resource_manager,
-        )
-        .unwrap();
+    /// Sets the game up from the assets loaded by a `LoadingScreen`
+    pub fn build(
+        width: u32,
+        height: u32,
+        mut audio: Audio,
+        mut resource_manager: ResourceManager,
+    ) -> Result<Self, Error> {
+        let music = resource_manager
+            .get_music(level_music(0))
+            .ok_or_else(|| Error::Other(format!("{}: music wasn't loaded", level_music(0))))?;
+        audio.play_music(&music, 0.0)?;
+
+        let level_data = resource_manager
+            .get_level_pack(LEVEL_PACK)
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "No level pack '{}' in {}",
+                    LEVEL_PACK, ASSET_MANIFEST
+                ))
+            })?
+            .to_vec()
+            .iter()
+            .map(|path| {
+                resource_manager
+                    .take_data::<LevelData>(path)
+                    .ok_or_else(|| Error::Other(format!("{}: level wasn't loaded", path)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let levels = level_data
+            .iter()
+            .map(|level| GameLevel::build(level, width, height / 2, &resource_manager))
+            .collect();
 
         let player_pos = glam::vec2(
             width as f32 / 2.0 - PLAYER_SIZE.x / 2.0,
             height as f32 - PLAYER_SIZE.y - PADDING,
         );
 
-        let player = GameObject::build(
+        // the first frames of the paddle sheet shimmer while idle, the rest flash on a hit
+        let paddle_frames = resource_manager.get_sheet_frames("paddle");
+        let (idle_frames, hit_frames) =
+            paddle_frames.split_at(paddle_frames.len().min(PADDLE_IDLE_FRAMES));
+        let paddle_idle = AnimationClip::uniform(
+            idle_frames,
+            PADDLE_IDLE_FRAME_DURATION,
+            PlaybackMode::PingPong,
+        )
+        .map_err(|e| e.in_entry("Sprite sheet", "paddle"))?;
+        let paddle_hit =
+            AnimationClip::uniform(hit_frames, PADDLE_HIT_FRAME_DURATION, PlaybackMode::Once)
+                .map_err(|e| e.in_entry("Sprite sheet", "paddle"))?;
+
+        let mut player = GameObject::build(
             player_pos,
             PLAYER_SIZE,
-            resource_manager.get_texture("paddle").unwrap(),
+            paddle_idle.first_region().clone(),
             None,
             None,
         );
+        let mut paddle_animation = AnimationPlayer::with_clip("idle", paddle_idle);
+        paddle_animation.add_clip("hit", paddle_hit);
+        player.set_animation(Some(paddle_animation));
 
         let ball_pos =
             player_pos + glam::vec2(PLAYER_SIZE.x / 2.0 - BALL_RADIUS, -BALL_RADIUS * 2.0);
 
-        let ball = BallObject::build(
+        let mut ball = BallObject::build(
             ball_pos,
             BALL_RADIUS,
             INITIAL_BALL_VELOCITY,
-            resource_manager.get_texture("face").unwrap(),
+            resource_manager.get_region("face").unwrap(),
         );
 
+        player.set_draw_order(Layer::World, PLAYER_ORDER);
+        ball.mut_game_object()
+            .set_draw_order(Layer::World, BALL_ORDER);
+
+        let brick_break = AnimationClip::uniform(
+            &resource_manager.get_sheet_frames("brick_break"),
+            BRICK_BREAK_FRAME_DURATION,
+            PlaybackMode::Once,
+        )
+        .map_err(|e| e.in_entry("Sprite sheet", "brick_break"))?;
+
+        let particle = resource_manager.get_region("particle").unwrap();
+
+        let ball_trail = ParticleEmitter::new(
+            EmitterConfig {
+                spawn_rate: 120.0,
+                lifetime: (0.3, 0.5),
+                speed: (5.0, 20.0),
+                inherit_velocity: -0.1,
+                position_jitter: BALL_RADIUS / 3.0,
+                color_start: glam::vec4(1.0, 0.7, 0.3, 0.8),
+                color_end: glam::vec4(1.0, 0.2, 0.1, 0.0),
+                size_start: BALL_RADIUS * 1.6,
+                size_end: BALL_RADIUS * 0.4,
+                blend: BlendMode::Additive,
+                layer: Layer::World,
+                sort_key: BALL_TRAIL_ORDER,
+                ..Default::default()
+            },
+            particle.clone(),
+            128,
+        );
+
+        let debris = ParticleEmitter::new(
+            EmitterConfig {
+                lifetime: (0.4, 0.8),
+                speed: (80.0, 250.0),
+                position_jitter: 15.0,
+                acceleration: glam::vec2(0.0, 600.0),
+                color_start: glam::vec4(1.0, 1.0, 1.0, 1.0),
+                color_end: glam::vec4(1.0, 1.0, 1.0, 0.0),
+                size_start: 10.0,
+                size_end: 3.0,
+                ..Default::default()
+            },
+            particle,
+            256,
+        );
+
+        let sprite_renderer = SpriteRenderer::new(
+            width,
+            height,
+            resource_manager.get_shader("sprite").unwrap(),
+        );
+        let post_processor = PostProcessor::new(
+            width,
+            height,
+            resource_manager.get_shader("post_processing").unwrap(),
+            MSAA_SAMPLES,
+        )?;
+
         Ok(Self {
             width,
             height,
             resource_manager,
+            audio,
             player,
             ball,
+            ball_trail,
+            debris,
+            debug_overlay: DebugOverlay::new(),
+            breaking_bricks: vec![],
+            brick_break,
             state: GameState::PLAYING,
-            sprite_renderer: SpriteRenderer::new(width, height),
-            levels: vec![one, two, three, four, five],
+            paused: false,
+            sprite_renderer,
+            post_processor,
+            camera: Camera2D::new(width, height),
+            time: 0.0,
+            levels,
+            level_data,
             level: 0,
+            lives: INITIAL_LIVES,
+            score: 0,
         })
     }
 
     pub fn play(&mut self, renderer: &mut Renderer) {
-        renderer.game_loop(&mut |event_pump, delta_time| {
-            self.process_input(event_pump, delta_time);
-            self.update(delta_time);
-            self.draw();
-        });
+        renderer.game_loop(self);
     }
 
     fn process_input(&mut self, event_pump: &EventPump, delta_time: f32) {
-        if self.state == GameState::PLAYING {
+        if self.state == GameState::PLAYING && !self.paused {
             let velocity = PLAYER_VELOCITY * delta_time;
 
             for scancode in event_pump.keyboard_state().pressed_scancodes() {
@@ -162,54 +371,294 @@ impl Game {
                                 self.width as f32 - self.player.size().x - PADDING;
                         }
                     }
-                    Scancode::Space => {
-                        self.ball.set_stuck(false);
-                    }
                     _ => {}
                 }
             }
         }
     }
 
-    fn update(&mut self, delta_time: f32) {
+    fn handle_event(&mut self, event: &Event) {
+        match self.state {
+            GameState::MENU => {
+                if event.is_key_pressed(Scancode::Return) {
+                    self.lives = INITIAL_LIVES;
+                    self.score = 0;
+                    self.state = GameState::PLAYING;
+                } else if event.is_key_pressed(Scancode::W) {
+                    self.level = (self.level + 1) % self.levels.len();
+                    self.play_level_music();
+                } else if event.is_key_pressed(Scancode::S) {
+                    self.level = (self.level + self.levels.len() - 1) % self.levels.len();
+                    self.play_level_music();
+                }
+            }
+            GameState::PLAYING => {
+                if event.is_key_pressed(Scancode::P) {
+                    self.paused = !self.paused;
+                } else if event.is_key_pressed(Scancode::Space) && !self.paused {
+                    self.ball.set_stuck(false);
+                }
+            }
+            GameState::WIN => {
+                if event.is_key_pressed(Scancode::Return) {
+                    self.state = GameState::MENU;
+                }
+            }
+        }
+
+        if event.is_key_pressed(Scancode::Grave) {
+            self.debug_overlay.toggle();
+        }
+
+        // Function keys toggle the layers in development builds
+        if cfg!(debug_assertions) {
+            let layers = [
+                (Scancode::F1, Layer::Background),
+                (Scancode::F2, Layer::World),
+                (Scancode::F3, Layer::Effects),
+                (Scancode::F4, Layer::Hud),
+                (Scancode::F5, Layer::Overlay),
+            ];
+
+            for (scancode, layer) in layers {
+                if event.is_key_pressed(scancode) {
+                    self.sprite_renderer.toggle_layer(layer);
+                }
+            }
+        }
+
+        // Losing focus while playing pauses the game so the ball doesn't get lost in the background
+        if *event == Event::FocusLost && self.state == GameState::PLAYING {
+            self.paused = true;
+        }
+    }
+
+    fn update_objects(&mut self, delta_time: f32) {
+        if self.state != GameState::PLAYING || self.paused {
+            return;
+        }
+
         self.ball.update(self.width, &self.player, delta_time);
         self.collide();
+        self.update_particles(delta_time);
+        self.update_animations(delta_time);
 
         if self.ball.game_object().position().y >= self.height as f32 {
+            self.play_sound("lost", &LOST_SOUND);
+            self.camera.add_trauma(BALL_LOST_TRAUMA);
+            self.lives -= 1;
+
+            if self.lives == 0 {
+                self.reset_level();
+                self.state = GameState::MENU;
+            }
+
+            self.reset_player();
+        }
+
+        if self.levels[self.level].is_completed() {
             self.reset_level();
             self.reset_player();
+            self.state = GameState::WIN;
+        }
+    }
+
+    fn update_particles(&mut self, delta_time: f32) {
+        let ball = self.ball.game_object();
+        let center = *ball.position() + self.ball.radius();
+
+        self.ball_trail
+            .update(delta_time, center, *ball.velocity(), !self.ball.stuck());
+        self.debris
+            .update(delta_time, glam::Vec2::ZERO, glam::Vec2::ZERO, false);
+    }
+
+    fn update_animations(&mut self, delta_time: f32) {
+        if let Some(animation) = self.player.mut_animation() {
+            animation.update(delta_time);
+
+            // the flash of a hit plays once, then the paddle goes back to idling
+            if animation.is_finished() {
+                animation.play("idle");
+            }
+        }
+
+        for brick in self.breaking_bricks.iter_mut() {
+            brick.update_animation(delta_time);
+        }
+
+        self.breaking_bricks.retain(|brick| {
+            brick
+                .animation()
+                .is_some_and(|animation| !animation.is_finished())
+        });
+    }
+
+    fn draw_scene(&mut self, alpha: f32) {
+        // chaos belongs to the win screen, so it follows the state that is actually shown
+        let won = self.state == GameState::WIN;
+        if self.post_processor.is_active(Effect::Chaos) != won {
+            self.post_processor.set_effect(Effect::Chaos, won);
+        }
+
+        self.post_processor.begin_render();
+
+        // the background isn't affected by the camera so shaking never reveals its edges
+        let view = self.camera.view_matrix();
+        self.sprite_renderer.set_view(Layer::World, view);
+        self.sprite_renderer.set_view(Layer::Effects, view);
+
+        self.sprite_renderer.set_layer(Layer::Background, 0);
+        self.sprite_renderer.draw_sprite(
+            &self.resource_manager.get_texture("background").unwrap(),
+            glam::vec2(0.0, 0.0),
+            glam::vec2(self.width as f32, self.height as f32),
+            0.0,
+            glam::vec3(1.0, 1.0, 1.0),
+        );
+
+        self.sprite_renderer.submit(&self.levels[self.level], alpha);
+        for brick in self.breaking_bricks.iter() {
+            self.sprite_renderer.submit(brick, alpha);
         }
+        self.sprite_renderer.submit(&self.player, alpha);
+        self.sprite_renderer.submit(&self.ball_trail, alpha);
+        self.sprite_renderer.submit(&self.ball, alpha);
+        self.sprite_renderer.submit(&self.debris, alpha);
+
+        self.sprite_renderer.flush();
+
+        self.post_processor.end_render();
+        self.post_processor.render(self.time);
+
+        // text is drawn on top of the processed scene so effects never make it unreadable
+        self.sprite_renderer.set_layer(Layer::Hud, 0);
+        self.draw_text();
+
+        if self.debug_overlay.is_visible() {
+            self.draw_debug_overlay(alpha);
+        }
+    }
+
+    /// Collision shapes follow the camera, the stats are flushed separately to stay put
+    fn draw_debug_overlay(&mut self, alpha: f32) {
+        self.sprite_renderer.set_layer(Layer::Overlay, 0);
+
+        self.sprite_renderer
+            .set_view(Layer::Overlay, self.camera.view_matrix());
+        self.debug_overlay.draw_world(
+            &mut self.sprite_renderer,
+            &self.levels[self.level],
+            &self.player,
+            &self.ball,
+            alpha,
+        );
+        self.sprite_renderer.flush();
+
+        self.sprite_renderer
+            .set_view(Layer::Overlay, glam::Mat4::IDENTITY);
+        self.debug_overlay.draw_stats(
+            &mut self.sprite_renderer,
+            self.resource_manager.get_font("hud").unwrap(),
+            self.height,
+        );
+        self.sprite_renderer.flush();
     }
 
-    fn draw(&mut self) {
-        if self.state == GameState::PLAYING {
-            // draw background
-            self.sprite_renderer.draw_sprite(
-                self.resource_manager.get_texture("background").unwrap(),
-                glam::vec2(0.0, 0.0),
-                glam::vec2(self.width as f32, self.height as f32),
-                0.0,
-                glam::vec3(1.0, 1.0, 1.0),
-            );
-            // draw level
-            self.levels[self.level].draw(&self.sprite_renderer);
-            // draw player
-            self.player.draw(&self.sprite_renderer);
-            // draw ball
-            self.ball.draw(&self.sprite_renderer);
+    fn draw_text(&mut self) {
+        let hud = self.resource_manager.get_font("hud").unwrap();
+        let title = self.resource_manager.get_font("title").unwrap();
+        let center = self.width as f32 / 2.0;
+        let middle = self.height as f32 / 2.0;
+
+        match self.state {
+            GameState::PLAYING => {
+                self.sprite_renderer.draw_text(
+                    hud,
+                    &format!("Lives: {}", self.lives),
+                    glam::vec2(PADDING, PADDING),
+                    &TextStyle::default(),
+                );
+                self.sprite_renderer.draw_text(
+                    hud,
+                    &format!("Score: {}", self.score),
+                    glam::vec2(self.width as f32 - PADDING, PADDING),
+                    &TextStyle {
+                        align: Align::Right,
+                        ..Default::default()
+                    },
+                );
+
+                if self.paused {
+                    self.sprite_renderer.draw_text(
+                        title,
+                        "Paused",
+                        glam::vec2(center, middle),
+                        &TextStyle {
+                            align: Align::Center,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+            GameState::MENU => {
+                self.sprite_renderer.draw_text(
+                    title,
+                    &format!("Level {}", self.level + 1),
+                    glam::vec2(center, middle - title.line_height()),
+                    &TextStyle {
+                        align: Align::Center,
+                        ..Default::default()
+                    },
+                );
+                self.sprite_renderer.draw_text(
+                    hud,
+                    "Press ENTER to start\nPress W or S to select level",
+                    glam::vec2(center, middle + PADDING),
+                    &TextStyle {
+                        align: Align::Center,
+                        ..Default::default()
+                    },
+                );
+            }
+            GameState::WIN => {
+                self.sprite_renderer.draw_text(
+                    title,
+                    "You WON!!!",
+                    glam::vec2(center, middle - title.line_height()),
+                    &TextStyle {
+                        align: Align::Center,
+                        color: glam::vec4(0.0, 1.0, 0.0, 1.0),
+                        ..Default::default()
+                    },
+                );
+                self.sprite_renderer.draw_text(
+                    hud,
+                    &format!("Final score: {}\nPress ENTER to retry", self.score),
+                    glam::vec2(center, middle + PADDING),
+                    &TextStyle {
+                        align: Align::Center,
+                        color: glam::vec4(1.0, 1.0, 0.0, 1.0),
+                        ..Default::default()
+                    },
+                );
+            }
         }
+
+        self.sprite_renderer.flush();
     }
 
     fn reset_level(&mut self) {
-        let current_level_path = format!("./src/game/levels/{}.level", self.level + 1);
+        self.ball_trail.clear();
+        self.debris.clear();
+        self.breaking_bricks.clear();
 
         self.levels[self.level] = GameLevel::build(
-            &current_level_path,
+            &self.level_data[self.level],
             self.width,
             self.height / 2,
             &self.resource_manager,
-        )
-        .unwrap();
+        );
     }
 
     fn reset_player(&mut self) {
@@ -218,62 +667,146 @@ impl Game {
             self.height as f32 - PLAYER_SIZE.y - PADDING,
         );
 
-        *self.player.mut_position() = player_pos;
+        self.player.set_position(player_pos);
 
         let ball_pos =
             player_pos + glam::vec2(PLAYER_SIZE.x / 2.0 - BALL_RADIUS, -BALL_RADIUS * 2.0);
 
-        self.ball.set_stuck(true);
-        *self.ball.mut_game_object().mut_position() = ball_pos;
+        self.ball.reset(ball_pos, INITIAL_BALL_VELOCITY);
+    }
+
+    fn play_sound(&mut self, name: &str, params: &SoundParams) {
+        if let Some(sound) = self.resource_manager.get_sound(name) {
+            self.audio.play(&sound, params);
+        }
+    }
+
+    fn play_level_music(&mut self) {
+        let Some(music) = self.resource_manager.get_music(level_music(self.level)) else {
+            eprintln!("Music {} wasn't loaded", level_music(self.level));
+            return;
+        };
+
+        if let Err(e) = self.audio.play_music(&music, MUSIC_CROSSFADE) {
+            eprintln!("Couldn't play the music of level {}: {}", self.level + 1, e);
+        }
     }
 
     fn collide(&mut self) {
-        for brick in self.levels[self.level].mut_bricks() {
+        for i in 0..self.levels[self.level].bricks().len() {
+            let brick = &mut self.levels[self.level].mut_bricks()[i];
+
             if !brick.destroyed() {
-                let collision = self.ball.collides(brick);
+                if let Some(collision) = self.ball.collides(brick) {
+                    self.debug_overlay.record_contact(&self.ball, &collision);
 
-                if collision.0 {
                     if !brick.is_solid() {
                         brick.set_destroyed(true);
+                        let (position, size, color) =
+                            (*brick.position(), *brick.size(), brick.color());
+
+                        self.score += BRICK_SCORE;
+                        self.play_sound("brick", &BRICK_SOUND);
+                        self.debris.burst(
+                            DEBRIS_PER_BRICK,
+                            position + size / 2.0,
+                            color.extend(1.0),
+                        );
+
+                        let mut breaking = GameObject::build(
+                            position,
+                            size,
+                            self.brick_break.first_region().clone(),
+                            Some(color),
+                            None,
+                        );
+                        breaking.set_draw_order(Layer::World, BREAKING_BRICK_ORDER);
+                        breaking.set_animation(Some(AnimationPlayer::with_clip(
+                            "break",
+                            self.brick_break.clone(),
+                        )));
+                        self.breaking_bricks.push(breaking);
+                    } else {
+                        self.camera.add_trauma(SOLID_TRAUMA);
+                        self.play_sound("solid", &SOLID_SOUND);
                     }
-                    let dir = collision.1;
-                    let diff_vector = collision.2;
 
-                    if dir == Direction::Left || dir == Direction::Right {
-                        self.ball.mut_game_object().mut_velocity().x =
-                            -self.ball.mut_game_object().velocity().x;
-                        let penetration = self.ball.radius() - diff_vector.x.abs();
+                    let velocity = self.ball.mut_game_object().mut_velocity();
 
-                        if dir == Direction::Left {
-                            self.ball.mut_game_object().mut_position().x += penetration;
-                        } else {
-                            self.ball.mut_game_object().mut_position().x -= penetration;
-                        }
+                    if collision.direction == Direction::Left
+                        || collision.direction == Direction::Right
+                    {
+                        velocity.x = -velocity.x;
                     } else {
-                        self.ball.mut_game_object().mut_velocity().y =
-                            -self.ball.mut_game_object().mut_velocity().y;
-
-                        let pentration = self.ball.radius() - diff_vector.y.abs();
-
-                        if dir == Direction::Up {
-                            self.ball.mut_game_object().mut_position().y -= pentration;
-                        } else {
-                            self.ball.mut_game_object().mut_position().y += pentration;
-                        }
+                        velocity.y = -velocity.y;
                     }
+
+                    *self.ball.mut_game_object().mut_position() += collision.penetration;
                 }
             }
         }
 
-        let result = self.ball.collides(&self.player);
+        if self.ball.stuck() {
+            return;
+        }
+
+        if let Some(collision) = self.ball.collides(&self.player) {
+            self.debug_overlay.record_contact(&self.ball, &collision);
+
+            // the ball can overlap the paddle for a few ticks, only the first one makes a sound
+            if self.ball.game_object().velocity().y > 0.0 {
+                self.play_sound("paddle", &PADDLE_SOUND);
+
+                if let Some(animation) = self.player.mut_animation() {
+                    animation.play("hit");
+                    animation.restart();
+                }
+            }
 
-        if !self.ball.stuck() && result.0 {
             self.ball.mut_game_object().mut_velocity().y =
-                -1.0 * self.ball.game_object().velocity().y.abs();
+                -self.ball.game_object().velocity().y.abs();
         }
     }
 }
 
+/// Levels alternate between the music tracks
+fn level_music(level: usize) -> &'static str {
+    const TRACKS: [&str; 2] = ["music_a", "music_b"];
+
+    TRACKS[level % TRACKS.len()]
+}
+
+impl GameLoop for Game {
+    fn process_events(&mut self, events: &[Event]) -> LoopControl {
+        for event in events {
+            match event {
+                Event::Quit => return LoopControl::Quit,
+                _ => self.handle_event(event),
+            }
+        }
+
+        LoopControl::Continue
+    }
+
+    fn update(&mut self, event_pump: &EventPump, delta_time: f32) {
+        self.player.snapshot_position();
+        self.ball.mut_game_object().snapshot_position();
+
+        self.process_input(event_pump, delta_time);
+        self.update_objects(delta_time);
+
+        self.time += delta_time;
+        self.camera.update(delta_time);
+        self.debug_overlay.update(delta_time);
+    }
+
+    fn draw(&mut self, alpha: f32) {
+        self.debug_overlay.tick_frame();
+        self.resource_manager.reload_changed_shaders();
+        self.draw_scene(alpha);
+    }
+}
+
 impl Drop for Game {
     fn drop(&mut self) {}
 }
@@ -1,4 +1,6 @@
-use engine::Renderer;
+use std::{path::PathBuf, process::ExitCode};
+
+use engine::{display::WindowMode, Audio, Error, GlDebug, PresentMode, Renderer, ScalingPolicy};
 
 mod engine;
 mod game;
@@ -6,18 +8,158 @@ mod game;
 const WINDOW_WIDTH: u32 = 800;
 const WINDOW_HEIGHT: u32 = 600;
 
-fn main() -> Result<(), String> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let args = parse_args()?;
+    let vfs = game::asset_vfs(args.assets);
+
     let mut renderer = Renderer::build("Breakout", WINDOW_WIDTH, WINDOW_HEIGHT)?;
+
+    if args.list_displays {
+        return print_displays(&renderer);
+    }
+
+    renderer.set_present_mode(args.present_mode);
     renderer.set_max_fps(120);
+    renderer.set_tick_rate(120);
     renderer.set_color(150, 150, 150);
 
-    let _ctx = renderer.window().gl_create_context()?;
-    // At the end of the day this defines the correct GL functions based on which OS we're compiling for.
-    gl::load_with(|name| renderer.window().subsystem().gl_get_proc_address(name) as *const _);
+    renderer.set_gl_debug(cfg!(debug_assertions).then(GlDebug::default));
+    renderer.create_gl_context()?;
+    renderer.set_scaling_policy(args.scaling);
+
+    if args.fullscreen {
+        enter_fullscreen(&mut renderer)?;
+    }
 
-    let mut game = game::Game::build(WINDOW_WIDTH, WINDOW_HEIGHT)?;
+    let loading_screen = game::LoadingScreen::build(WINDOW_WIDTH, WINDOW_HEIGHT, vfs)?;
+    let resource_manager = match loading_screen.run(&mut renderer)? {
+        Some(resource_manager) => resource_manager,
+        // The window was closed while loading
+        None => return Ok(()),
+    };
+
+    let audio = Audio::build(renderer.sdl());
+    let mut game = game::Game::build(WINDOW_WIDTH, WINDOW_HEIGHT, audio, resource_manager)?;
 
     game.play(&mut renderer);
 
     Ok(())
 }
+
+const USAGE: &str =
+    "usage: breakout [--assets <dir or archive>] [--scaling stretch|letterbox|integer] [--present-mode vsync|adaptive|uncapped] [--fullscreen] [--list-displays]";
+
+struct Args {
+    /// Directory or archive whose files take precedence over all other assets
+    assets: Option<PathBuf>,
+    scaling: ScalingPolicy,
+    present_mode: PresentMode,
+    /// Starts in exclusive fullscreen instead of a window
+    fullscreen: bool,
+    /// Prints the monitors and their display modes instead of running the game
+    list_displays: bool,
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let mut args = std::env::args_os().skip(1);
+    let mut parsed = Args {
+        assets: None,
+        scaling: ScalingPolicy::Letterbox,
+        present_mode: PresentMode::AdaptiveVSync,
+        fullscreen: false,
+        list_displays: false,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |message: &str| {
+            args.next()
+                .ok_or_else(|| Error::Other(format!("{}, {}", message, USAGE)))
+        };
+
+        match arg.to_str() {
+            Some("--assets") => {
+                parsed.assets = Some(PathBuf::from(value(
+                    "--assets needs a directory or an archive",
+                )?));
+            }
+            Some("--scaling") => {
+                parsed.scaling = match value("--scaling needs a policy")?.to_str() {
+                    Some("stretch") => ScalingPolicy::Stretch,
+                    Some("letterbox") => ScalingPolicy::Letterbox,
+                    Some("integer") => ScalingPolicy::IntegerScale,
+                    _ => return Err(Error::Other(format!("Unknown scaling policy, {}", USAGE))),
+                };
+            }
+            Some("--present-mode") => {
+                parsed.present_mode = match value("--present-mode needs a mode")?.to_str() {
+                    Some("vsync") => PresentMode::VSync,
+                    Some("adaptive") => PresentMode::AdaptiveVSync,
+                    Some("uncapped") => PresentMode::Uncapped,
+                    _ => return Err(Error::Other(format!("Unknown present mode, {}", USAGE))),
+                };
+            }
+            Some("--fullscreen") => parsed.fullscreen = true,
+            Some("--list-displays") => parsed.list_displays = true,
+            _ => {
+                return Err(Error::Other(format!(
+                    "Unknown argument '{}', {}",
+                    arg.to_string_lossy(),
+                    USAGE
+                )))
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Exclusive fullscreen in the best mode of the monitor the window is on, SDL lists the largest
+/// resolution with the highest refresh rate first
+fn enter_fullscreen(renderer: &mut Renderer) -> Result<(), Error> {
+    let modes = renderer.display_modes(renderer.current_display()?)?;
+
+    if let Some(mode) = modes.first() {
+        renderer.set_display_mode(*mode)?;
+    }
+
+    renderer.set_window_mode(WindowMode::Fullscreen)
+}
+
+fn print_displays(renderer: &Renderer) -> Result<(), Error> {
+    for display in renderer.displays()? {
+        let (x, y, width, height) = display.bounds;
+        let dpi = display
+            .dpi
+            .map_or(String::new(), |dpi| format!(", {:.0} dpi", dpi));
+
+        println!(
+            "{}: {}, {}x{} at {},{}{}",
+            display.index, display.name, width, height, x, y, dpi
+        );
+
+        for mode in renderer.display_modes(display.index)? {
+            let current = if mode == display.current_mode {
+                " (current)"
+            } else {
+                ""
+            };
+
+            println!(
+                "    {}x{} {}Hz{}",
+                mode.width, mode.height, mode.refresh_rate, current
+            );
+        }
+    }
+
+    Ok(())
+}